@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::de::Error as _;
 use serde_json::Value;
 use snafu::ResultExt;
@@ -5,10 +7,45 @@ use snafu::Snafu;
 
 const API_URL: &str = "https://api.twelvedata.com/price";
 
-pub async fn get_realtime_price(api_key: &str, symbol: &str) -> Result<f64, Error> {
-    let client = reqwest::Client::new();
+/// The prices of every symbol by symbol, in one request with comma separated symbols.
+pub async fn get_realtime_prices(
+    api_key: &str,
+    symbols: &[&str],
+) -> Result<HashMap<String, f64>, Error> {
+    get_batch_prices(API_URL, api_key, symbols).await
+}
+
+async fn get_batch_prices(
+    url: &str,
+    api_key: &str,
+    symbols: &[&str],
+) -> Result<HashMap<String, f64>, Error> {
+    let (json, text) = request(url, api_key, &symbols.join(",")).await?;
+
+    // Keyed by symbol, e.g. `{"USD/CNH": {"price": "7.1"}, "USD/CNY": {"price": "7.2"}}`,
+    // except for a single symbol which is answered unkeyed, e.g. `{"price": "7.1"}`.
+    if let [symbol] = symbols {
+        return Ok(HashMap::from([(
+            symbol.to_string(),
+            parse_price(&json, &text)?,
+        )]));
+    }
+    symbols
+        .iter()
+        .map(|symbol| {
+            let quote = json
+                .get(symbol)
+                .ok_or(serde_json::Error::custom(format!("{} missing", symbol)))
+                .context(ParseJsonSnafu { text: text.clone() })?;
+            Ok((symbol.to_string(), parse_price(quote, &text)?))
+        })
+        .collect()
+}
+
+async fn request(url: &str, api_key: &str, symbol: &str) -> Result<(Value, String), Error> {
+    let client = crate::http::client();
     let response = client
-        .get(API_URL)
+        .get(url)
         .query(&[("symbol", symbol), ("apikey", api_key)])
         .send()
         .await
@@ -19,20 +56,20 @@ pub async fn get_realtime_price(api_key: &str, symbol: &str) -> Result<f64, Erro
         text: response.clone(),
     })?;
 
+    Ok((json, response))
+}
+
+fn parse_price(json: &Value, text: &str) -> Result<f64, Error> {
     let price = json
         .get("price")
         .and_then(Value::as_str)
         .ok_or(serde_json::Error::custom("price not a string"))
-        .context(ParseJsonSnafu {
-            text: response.clone(),
-        })?;
+        .context(ParseJsonSnafu { text })?;
 
     let price = price
         .parse::<f64>()
         .map_err(|_| serde_json::Error::custom("price not a number"))
-        .context(ParseJsonSnafu {
-            text: response.clone(),
-        })?;
+        .context(ParseJsonSnafu { text })?;
 
     Ok(price)
 }
@@ -54,10 +91,66 @@ mod tests {
     use super::*;
 
     #[tokio::test]
-    async fn test_get_realtime_price() {
-        let api_key = "demo";
-        let symbol = "USD/JPY";
-        let price = get_realtime_price(api_key, symbol).await.unwrap();
-        assert!(price > 0.0);
+    async fn test_get_batch_prices() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/price")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("symbol".into(), "USD/CNH,USD/CNY".into()),
+                mockito::Matcher::UrlEncoded("apikey".into(), "demo".into()),
+            ]))
+            .with_body(r#"{"USD/CNH":{"price":"7.12"},"USD/CNY":{"price":"7.11"}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let prices = get_batch_prices(
+            &format!("{}/price", server.url()),
+            "demo",
+            &["USD/CNH", "USD/CNY"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(prices["USD/CNH"], 7.12);
+        assert_eq!(prices["USD/CNY"], 7.11);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_batch_prices_single_symbol() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/price")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "symbol".into(),
+                "USD/CNH".into(),
+            ))
+            .with_body(r#"{"price":"7.12"}"#)
+            .create_async()
+            .await;
+
+        let prices = get_batch_prices(&format!("{}/price", server.url()), "demo", &["USD/CNH"])
+            .await
+            .unwrap();
+        assert_eq!(prices["USD/CNH"], 7.12);
+    }
+
+    #[tokio::test]
+    async fn test_get_batch_prices_missing_symbol() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/price")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"USD/CNH":{"price":"7.12"},"USD/CNY":{"code":400,"status":"error"}}"#)
+            .create_async()
+            .await;
+
+        let result = get_batch_prices(
+            &format!("{}/price", server.url()),
+            "demo",
+            &["USD/CNH", "USD/CNY"],
+        )
+        .await;
+        assert!(matches!(result, Err(Error::ParseJson { .. })));
     }
 }
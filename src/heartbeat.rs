@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+
+/// The `[heartbeat]` section: pings a dead man's switch such as <https://healthchecks.io>
+/// after every poll, so an outside service notices when the monitor itself stops.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Heartbeat {
+    /// Pinged after a successful poll, `{url}/fail` after a failed one.
+    pub url: String,
+    /// Seconds allowed for each ping.
+    #[serde(default = "default_timeout")]
+    pub timeout: f64,
+}
+
+fn default_timeout() -> f64 {
+    10.0
+}
+
+impl Heartbeat {
+    pub async fn ping(&self, success: bool) -> Result<(), Error> {
+        let url = self.url.trim_end_matches('/');
+        let url = if success {
+            url.to_string()
+        } else {
+            format!("{}/fail", url)
+        };
+
+        crate::http::client()
+            .get(&url)
+            .timeout(Duration::from_secs_f64(self.timeout))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .context(PingSnafu { url })?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to ping the heartbeat {}: {}", url, source))]
+    Ping { url: String, source: reqwest::Error },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ping() {
+        let mut server = mockito::Server::new_async().await;
+        let success = server
+            .mock("GET", "/ping/uuid")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+        let fail = server
+            .mock("GET", "/ping/uuid/fail")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let heartbeat = Heartbeat {
+            url: format!("{}/ping/uuid/", server.url()),
+            timeout: default_timeout(),
+        };
+        heartbeat.ping(true).await.unwrap();
+        heartbeat.ping(false).await.unwrap();
+        success.assert_async().await;
+        fail.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_ping_error_status() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/ping/uuid")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let heartbeat = Heartbeat {
+            url: format!("{}/ping/uuid", server.url()),
+            timeout: default_timeout(),
+        };
+        assert!(matches!(
+            heartbeat.ping(true).await,
+            Err(Error::Ping { .. })
+        ));
+    }
+}
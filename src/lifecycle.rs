@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{monitor::Level, notify::Message};
+
+/// The `[lifecycle]` section: status messages when the monitor starts and stops.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Lifecycle {
+    /// Sent after the first successful poll, with the current rate and thresholds.
+    #[serde(default)]
+    pub startup: bool,
+    /// Sent on Ctrl+C or SIGTERM, with the last known rate.
+    #[serde(default)]
+    pub shutdown: bool,
+    /// Names of the notifiers to send the messages to, every notifier when empty.
+    #[serde(default)]
+    pub notifiers: Vec<String>,
+}
+
+fn describe_levels(levels: &[Level]) -> String {
+    levels
+        .iter()
+        .map(|level| format!("{} {:.3}", level.name, level.threshold * 100.0))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+impl Lifecycle {
+    pub fn startup_message(&self, monitor: &str, rate: f64, levels: &[Level]) -> Option<Message> {
+        if !self.startup {
+            return None;
+        }
+
+        let mut text = format!(
+            "CNH/CNY汇率监控已启动，当前为:{:.3}，阈值: {}",
+            rate * 100.0,
+            describe_levels(levels)
+        );
+        let breached = levels
+            .iter()
+            .filter(|level| rate < level.threshold)
+            .min_by(|a, b| a.threshold.total_cmp(&b.threshold));
+        if let Some(level) = breached {
+            text.push_str(&format!("，当前已低于{}阈值", level.name));
+        }
+        Some(Message::new(monitor, &text, breached.is_some(), rate))
+    }
+
+    pub fn shutdown_message(
+        &self,
+        monitor: &str,
+        rate: Option<f64>,
+        levels: &[Level],
+    ) -> Option<Message> {
+        if !self.shutdown {
+            return None;
+        }
+
+        let last = match rate {
+            Some(rate) => format!("最新为:{:.3}", rate * 100.0),
+            None => "尚无汇率数据".to_string(),
+        };
+        let text = format!(
+            "CNH/CNY汇率监控已停止，{}，阈值: {}",
+            last,
+            describe_levels(levels)
+        );
+        let under_threshold =
+            rate.is_some_and(|rate| levels.iter().any(|level| rate < level.threshold));
+        Some(Message::new(
+            monitor,
+            &text,
+            under_threshold,
+            rate.unwrap_or_default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::notify::Severity;
+
+    use super::*;
+
+    fn levels() -> Vec<Level> {
+        vec![
+            Level {
+                name: "warning".to_string(),
+                threshold: 0.998,
+                severity: Severity::Warning,
+            },
+            Level {
+                name: "critical".to_string(),
+                threshold: 0.990,
+                severity: Severity::Critical,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_startup_message() {
+        assert!(Lifecycle::default()
+            .startup_message("cnh_cny", 0.999, &levels())
+            .is_none());
+
+        let lifecycle = Lifecycle {
+            startup: true,
+            ..Default::default()
+        };
+        let message = lifecycle
+            .startup_message("cnh_cny", 0.999, &levels())
+            .unwrap();
+        assert_eq!(
+            message.text,
+            "CNH/CNY汇率监控已启动，当前为:99.900，阈值: warning 99.800 / critical 99.000"
+        );
+        assert!(!message.under_threshold);
+
+        let message = lifecycle
+            .startup_message("cnh_cny", 0.997, &levels())
+            .unwrap();
+        assert!(message.text.ends_with("，当前已低于warning阈值"));
+        assert!(message.under_threshold);
+    }
+
+    #[test]
+    fn test_shutdown_message() {
+        let lifecycle = Lifecycle {
+            shutdown: true,
+            ..Default::default()
+        };
+        let message = lifecycle
+            .shutdown_message("cnh_cny", None, &levels())
+            .unwrap();
+        assert_eq!(
+            message.text,
+            "CNH/CNY汇率监控已停止，尚无汇率数据，阈值: warning 99.800 / critical 99.000"
+        );
+        assert!(!message.under_threshold);
+
+        let message = lifecycle
+            .shutdown_message("cnh_cny", Some(0.985), &levels())
+            .unwrap();
+        assert!(message.text.contains("最新为:98.500"));
+        assert!(message.under_threshold);
+    }
+}
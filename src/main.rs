@@ -1,16 +1,31 @@
-use std::str::FromStr;
+use std::{str::FromStr, sync::Mutex};
 
+use chrono::Utc;
 use directories::ProjectDirs;
-use notify::{Notify, NotifyType};
+use monitor::{Ladder, Transition};
+use notify::{
+    dispatch::{DeliveryReport, Dispatcher},
+    throttle::Throttle,
+    Message,
+};
+use report::{run_reports, Tracker};
+use setting::Setting;
 use tokio::signal;
-use tracing::{debug, info, level_filters::LevelFilter, warn};
+use tracing::{info, level_filters::LevelFilter, warn};
+use watchdog::FeedHealth;
 mod forex;
+mod heartbeat;
+mod http;
+mod lifecycle;
+mod monitor;
 mod notify;
+mod report;
 mod setting;
+mod watchdog;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let setting = setting::Setting::new(
+    let setting = Setting::new(
         "FOREX_NOTIFY_CONFIG",
         ProjectDirs::from("me", "939", "forex_notify"),
     )
@@ -21,91 +36,212 @@ async fn main() {
 
     info!("Starting the CNH/CNY forex rate monitor");
 
+    let dispatcher = Dispatcher {
+        notifiers: &setting.notifiers,
+        rules: &setting.rules,
+        retry: &setting.retry,
+        timeout: setting.timeout,
+        outbox: setting.outbox.as_ref(),
+        throttle: Throttle::new(setting.dedup_window, setting.rate_limit.clone()),
+    };
+
+    let tracker = Tracker::new(setting.reports.len());
+    let health = Mutex::new(FeedHealth::new(Utc::now()));
+
     tokio::select! {
         _ = shutdown_signal() => {},
-        _ = run_forex(&setting.api_key,setting.warning_threshold,setting.sleeptime,setting.notifiers) => {},
+        _ = run_forex(&setting,&dispatcher,&tracker,&health) => {},
+        _ = run_reports(&setting.reports,&tracker,&dispatcher) => {},
+        _ = run_watchdog(&setting,&dispatcher,&health) => {},
     }
 
     info!("Shutting down the CNH/CNY forex rate monitor");
+    let message =
+        setting
+            .lifecycle
+            .shutdown_message(MONITOR, tracker.last(MONITOR), &setting.levels());
+    if let Some(message) = message {
+        send_status(&dispatcher, &setting.lifecycle.notifiers, &message).await;
+    }
+}
+
+const MONITOR: &str = "cnh_cny";
+
+/// Sends a status message about the monitor itself, outside of the rules and rate limits.
+async fn send_status(dispatcher: &Dispatcher<'_>, notifiers: &[String], message: &Message) {
+    let report = DeliveryReport {
+        monitor: MONITOR.to_string(),
+        deliveries: dispatcher.send_to(notifiers, message).await,
+    };
+    report.log();
 }
 
 async fn run_forex(
-    api_key: &str,
-    warning_threshold: f64,
-    sleeptime: u64,
-    notifiers: Vec<NotifyType>,
+    setting: &Setting,
+    dispatcher: &Dispatcher<'_>,
+    tracker: &Tracker,
+    health: &Mutex<FeedHealth>,
 ) {
     const CURRENCY1: &str = "USD/CNH";
     const CURRENCY2: &str = "USD/CNY";
 
-    let mut under_threshold = false;
+    let (api_key, sleeptime) = (&setting.api_key, setting.sleeptime);
+    let mut ladder = Ladder::new(setting.levels());
+    let mut started = false;
     loop {
-        let price1 = forex::get_realtime_price(api_key, CURRENCY1)
+        let prices = forex::get_realtime_prices(api_key, &[CURRENCY1, CURRENCY2])
             .await
             .inspect_err(|e| {
-                warn!("Failed to get the price of {}: {}", CURRENCY1, e);
-            });
-        let price2 = forex::get_realtime_price(api_key, CURRENCY2)
-            .await
-            .inspect_err(|e| {
-                warn!("Failed to get the price of {}: {}", CURRENCY2, e);
+                warn!(
+                    "Failed to get the prices of {} and {}: {}",
+                    CURRENCY1, CURRENCY2, e
+                );
             });
-        if price1.is_err() || price2.is_err() {
+        let Ok(prices) = prices else {
+            if let Some(watchdog) = setting.watchdog.as_ref() {
+                let message = health
+                    .lock()
+                    .unwrap()
+                    .failure(watchdog, MONITOR, Utc::now());
+                if let Some(message) = message {
+                    warn!("The forex provider is down: {}", message.text);
+                    send_status(dispatcher, &watchdog.notifiers, &message).await;
+                }
+            }
+            ping(setting, false).await;
             tokio::time::sleep(tokio::time::Duration::from_secs(sleeptime)).await;
             continue;
+        };
+        let cnh_cny = prices[CURRENCY2] / prices[CURRENCY1];
+
+        if let Some(watchdog) = setting.watchdog.as_ref() {
+            let message = health
+                .lock()
+                .unwrap()
+                .success(watchdog, MONITOR, cnh_cny, Utc::now());
+            if let Some(message) = message {
+                info!("The forex provider has recovered");
+                send_status(dispatcher, &watchdog.notifiers, &message).await;
+            }
+        }
+
+        dispatcher.record_rate(MONITOR, cnh_cny).await;
+
+        // A rate already below a threshold still breaches it on this first poll,
+        // the startup message only adds the context.
+        if !started {
+            started = true;
+            let lifecycle = &setting.lifecycle;
+            if let Some(message) = lifecycle.startup_message(MONITOR, cnh_cny, ladder.levels()) {
+                send_status(dispatcher, &lifecycle.notifiers, &message).await;
+            }
+        }
+
+        if let Some(threshold) = ladder.next_threshold(cnh_cny) {
+            tracker.record_rate(MONITOR, cnh_cny, threshold);
         }
-        let cnh_cny = price2.unwrap() / price1.unwrap();
 
-        if cnh_cny < warning_threshold {
-            if !under_threshold {
+        let message = match ladder.update(cnh_cny) {
+            Some(Transition::Breached(level)) => {
+                tracker.record_alert(MONITOR);
                 info!(
-                    "CNH/CNY is below the warning threshold: {:.3}",
+                    "CNH/CNY is below the {} threshold: {:.3}",
+                    level.name,
                     cnh_cny * 100.0
                 );
-                under_threshold = true;
-                let message = format!("CNH/CNY低于预设值，为:{:.3}", (cnh_cny * 100.0));
-                for notifier in notifiers.iter() {
-                    let ret = if let NotifyType::Webhook(webhook) = notifier {
-                        let message = webhook.generate_message(under_threshold, cnh_cny);
-                        webhook.send_message(&message).await
-                    } else {
-                        notifier.send_message(&message).await
-                    };
-
-                    let _ = ret
-                        .inspect_err(|e| {
-                            warn!("Failed to send the message use {:?}: {}", notifier, e);
-                        })
-                        .inspect(|_| debug!("Successfully sent the message use {:?}", notifier));
-                }
+                Some(
+                    Message::new(
+                        MONITOR,
+                        &format!(
+                            "CNH/CNY低于{}阈值{:.3}，为:{:.3}",
+                            level.name,
+                            level.threshold * 100.0,
+                            cnh_cny * 100.0
+                        ),
+                        true,
+                        cnh_cny,
+                    )
+                    .with_level(&level.name, level.severity),
+                )
             }
-        } else if under_threshold {
-            info!(
-                "CNH/CNY is above the warning threshold: {:.3}",
-                cnh_cny * 100.0
-            );
-            under_threshold = false;
-            let message = format!("CNH/CNY高于预设值，为:{:.3}", (cnh_cny * 100.0));
-            for notifier in notifiers.iter() {
-                let ret = if let NotifyType::Webhook(webhook) = notifier {
-                    let message = webhook.generate_message(under_threshold, cnh_cny);
-                    webhook.send_message(&message).await
-                } else {
-                    notifier.send_message(&message).await
+            Some(Transition::Recovered { from, to }) => {
+                info!(
+                    "CNH/CNY is above the {} threshold: {:.3}",
+                    from.name,
+                    cnh_cny * 100.0
+                );
+                let text = match to {
+                    Some(to) => format!(
+                        "CNH/CNY回升至{}阈值以上，仍低于{}阈值，为:{:.3}",
+                        from.name,
+                        to.name,
+                        cnh_cny * 100.0
+                    ),
+                    None => format!("CNH/CNY高于预设值，为:{:.3}", (cnh_cny * 100.0)),
                 };
-
-                let _ = ret
-                    .inspect_err(|e| {
-                        warn!("Failed to send the message use {:?}: {}", notifier, e);
-                    })
-                    .inspect(|_| debug!("Successfully sent the message use {:?}", notifier));
+                let message = Message::new(MONITOR, &text, to.is_some(), cnh_cny).recovered();
+                // Still below a lower level, which keeps routing the alert by its severity.
+                Some(match to {
+                    Some(to) => message.with_level(&to.name, to.severity),
+                    None => message,
+                })
             }
+            None => None,
+        };
+        let report = DeliveryReport {
+            monitor: MONITOR.to_string(),
+            deliveries: dispatcher.dispatch(message.as_ref()).await,
+        };
+        if !report.deliveries.is_empty() {
+            report.log();
         }
+        ping(setting, true).await;
 
         tokio::time::sleep(tokio::time::Duration::from_secs(sleeptime)).await;
     }
 }
 
+/// Fires the `stale_after` alert of the watchdog on its own timer, so it also goes out
+/// when a poll hangs instead of failing.
+async fn run_watchdog(setting: &Setting, dispatcher: &Dispatcher<'_>, health: &Mutex<FeedHealth>) {
+    let Some(watchdog) = setting
+        .watchdog
+        .as_ref()
+        .filter(|w| w.stale_after.is_some())
+    else {
+        return std::future::pending().await;
+    };
+
+    loop {
+        let now = Utc::now();
+        let (message, deadline) = {
+            let mut health = health.lock().unwrap();
+            let message = health.check_stale(watchdog, MONITOR, now);
+            (message, health.stale_deadline(watchdog).unwrap_or(now))
+        };
+        if let Some(message) = message {
+            warn!("The forex provider is down: {}", message.text);
+            send_status(dispatcher, &watchdog.notifiers, &message).await;
+        }
+
+        // Once stale, wait a whole window before checking for the next outage.
+        let wait = match (deadline - now).to_std() {
+            Ok(wait) if !wait.is_zero() => wait,
+            _ => std::time::Duration::from_secs(watchdog.stale_after.unwrap_or_default()),
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Tells the heartbeat service whether the poll cycle succeeded, when one is configured.
+async fn ping(setting: &Setting, success: bool) {
+    if let Some(heartbeat) = setting.heartbeat.as_ref() {
+        let _ = heartbeat.ping(success).await.inspect_err(|e| {
+            warn!("{}", e);
+        });
+    }
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
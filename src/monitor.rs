@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+
+use crate::notify::Severity;
+
+/// An entry of `[[levels]]`: an alert fires once when the rate falls below `threshold`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Level {
+    pub name: String,
+    pub threshold: f64,
+    #[serde(default = "default_severity")]
+    pub severity: Severity,
+}
+
+fn default_severity() -> Severity {
+    Severity::Warning
+}
+
+/// What changed in a [`Ladder`] after a new rate.
+#[derive(Debug)]
+pub enum Transition<'a> {
+    /// The rate fell below one or more levels, the most severe of them is reported.
+    Breached(&'a Level),
+    /// The rate rose back above `from`, `to` is the most severe level still breached.
+    Recovered {
+        from: &'a Level,
+        to: Option<&'a Level>,
+    },
+}
+
+/// Tracks which levels are breached. Each level fires once when crossed
+/// and re-arms on its own once the rate is back above its threshold.
+#[derive(Debug)]
+pub struct Ladder {
+    /// Sorted by descending threshold, so later levels are further away.
+    levels: Vec<Level>,
+    breached: Vec<bool>,
+}
+
+impl Ladder {
+    pub fn new(mut levels: Vec<Level>) -> Self {
+        levels.sort_by(|a, b| b.threshold.total_cmp(&a.threshold));
+        let breached = vec![false; levels.len()];
+        Self { levels, breached }
+    }
+
+    /// The levels sorted by descending threshold.
+    pub fn levels(&self) -> &[Level] {
+        &self.levels
+    }
+
+    /// The next threshold `rate` would cross, or the furthest one once all are breached.
+    pub fn next_threshold(&self, rate: f64) -> Option<f64> {
+        self.levels
+            .iter()
+            .map(|level| level.threshold)
+            .find(|threshold| rate >= *threshold)
+            .or_else(|| self.levels.last().map(|level| level.threshold))
+    }
+
+    pub fn update(&mut self, rate: f64) -> Option<Transition<'_>> {
+        let previous = self.breached.iter().rposition(|breached| *breached);
+        let mut newly_breached = None;
+        for (index, level) in self.levels.iter().enumerate() {
+            let breached = rate < level.threshold;
+            if breached && !self.breached[index] {
+                newly_breached = Some(index);
+            }
+            self.breached[index] = breached;
+        }
+        let current = self.breached.iter().rposition(|breached| *breached);
+
+        match (newly_breached, previous) {
+            (Some(index), _) => Some(Transition::Breached(&self.levels[index])),
+            (None, Some(previous)) if current < Some(previous) => Some(Transition::Recovered {
+                from: &self.levels[previous],
+                to: current.map(|index| &self.levels[index]),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ladder() -> Ladder {
+        let level = |name: &str, threshold, severity| Level {
+            name: name.to_string(),
+            threshold,
+            severity,
+        };
+        Ladder::new(vec![
+            level("critical", 0.990, Severity::Critical),
+            level("notice", 0.998, Severity::Info),
+            level("warning", 0.995, Severity::Warning),
+        ])
+    }
+
+    fn breached(transition: Option<Transition>) -> Option<String> {
+        match transition {
+            Some(Transition::Breached(level)) => Some(level.name.clone()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_escalation() {
+        let mut ladder = ladder();
+        assert!(ladder.update(0.999).is_none());
+        assert_eq!(breached(ladder.update(0.997)).as_deref(), Some("notice"));
+        assert!(ladder.update(0.996).is_none());
+        assert_eq!(breached(ladder.update(0.994)).as_deref(), Some("warning"));
+        assert_eq!(breached(ladder.update(0.980)).as_deref(), Some("critical"));
+        assert!(ladder.update(0.985).is_none());
+    }
+
+    #[test]
+    fn test_next_threshold() {
+        let ladder = ladder();
+        assert_eq!(ladder.next_threshold(0.999), Some(0.998));
+        assert_eq!(ladder.next_threshold(0.996), Some(0.995));
+        assert_eq!(ladder.next_threshold(0.985), Some(0.990));
+        assert_eq!(Ladder::new(Vec::new()).next_threshold(0.999), None);
+    }
+
+    #[test]
+    fn test_skip_levels() {
+        let mut ladder = ladder();
+        assert_eq!(breached(ladder.update(0.985)).as_deref(), Some("critical"));
+        assert!(ladder.update(0.989).is_none());
+    }
+
+    #[test]
+    fn test_rearm() {
+        let mut ladder = ladder();
+        ladder.update(0.994);
+
+        match ladder.update(0.996) {
+            Some(Transition::Recovered { from, to }) => {
+                assert_eq!(from.name, "warning");
+                assert_eq!(to.unwrap().name, "notice");
+            }
+            other => panic!("unexpected transition {:?}", other),
+        }
+        // The warning level re-armed on its own and fires again.
+        assert_eq!(breached(ladder.update(0.994)).as_deref(), Some("warning"));
+
+        match ladder.update(0.999) {
+            Some(Transition::Recovered { from, to }) => {
+                assert_eq!(from.name, "warning");
+                assert!(to.is_none());
+            }
+            other => panic!("unexpected transition {:?}", other),
+        }
+        assert!(ladder.update(0.999).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::*;
+
+/// An iOS Bark push, see <https://bark.day.app/#/tutorial>.
+#[derive(Deserialize, Serialize)]
+pub struct Bark {
+    /// The Bark server, defaults to the public one.
+    #[serde(default = "default_url")]
+    url: String,
+    device_key: String,
+    title: Option<String>,
+    sound: Option<String>,
+    /// `active`, `timeSensitive`, `passive` or `critical`.
+    level: Option<String>,
+    group: Option<String>,
+    icon: Option<String>,
+    /// Opened when the notification is tapped.
+    click: Option<String>,
+}
+
+fn default_url() -> String {
+    "https://api.day.app".to_string()
+}
+
+impl Debug for Bark {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bark")
+            .field("url", &self.url)
+            .field("title", &self.title)
+            .field("level", &self.level)
+            .field("group", &self.group)
+            .finish()
+    }
+}
+
+impl Bark {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, device_key: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            device_key: device_key.to_string(),
+            title: None,
+            sound: None,
+            level: None,
+            group: None,
+            icon: None,
+            click: None,
+        }
+    }
+
+    fn get_body(&self, message: &Message) -> Value {
+        let mut body = json!({
+            "device_key": self.device_key,
+            "title": self.title.as_deref().unwrap_or(DEFAULT_TITLE),
+            "body": message.text,
+        });
+        for (key, value) in [
+            ("sound", &self.sound),
+            ("level", &self.level),
+            ("group", &self.group),
+            ("icon", &self.icon),
+            ("url", &self.click),
+        ] {
+            if let Some(value) = value {
+                body[key] = json!(value);
+            }
+        }
+
+        body
+    }
+}
+
+#[async_trait]
+impl Notify for Bark {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let client = crate::http::client();
+        let response = client
+            .post(format!("{}/push", self.url.trim_end_matches('/')))
+            .json(&self.get_body(message))
+            .send()
+            .await
+            .context(NetworkSnafu)?;
+
+        check_json_code(response, &["code"], 200).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/push")
+            .match_body(mockito::Matcher::Json(json!({
+                "device_key": "key",
+                "title": DEFAULT_TITLE,
+                "body": "低于预设值",
+                "sound": "alarm",
+                "level": "timeSensitive",
+                "group": "forex",
+            })))
+            .with_status(200)
+            .with_body(r#"{"code":200,"message":"success"}"#)
+            .create_async()
+            .await;
+
+        let mut bark = Bark::new(&format!("{}/", server.url()), "key");
+        bark.sound = Some("alarm".to_string());
+        bark.level = Some("timeSensitive".to_string());
+        bark.group = Some("forex".to_string());
+        bark.send_message(&Message::new("cnh_cny", "低于预设值", true, 0.997))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,148 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::*;
+
+/// A DingTalk group robot, see <https://open.dingtalk.com/document/robots/custom-robot-access>.
+#[derive(Deserialize, Serialize)]
+pub struct DingTalk {
+    /// The webhook URL including the `access_token` query parameter.
+    url: String,
+    /// The signing secret (`SEC...`), required when the robot uses signature verification.
+    secret: Option<String>,
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    at_mobiles: Vec<String>,
+    #[serde(default)]
+    at_all: bool,
+}
+
+impl Debug for DingTalk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DingTalk")
+            .field("title", &self.title)
+            .field("at_mobiles", &self.at_mobiles)
+            .field("at_all", &self.at_all)
+            .finish()
+    }
+}
+
+impl DingTalk {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, secret: Option<&str>, title: Option<&str>) -> Self {
+        Self {
+            url: url.to_string(),
+            secret: secret.map(|s| s.to_string()),
+            title: title.map(|s| s.to_string()),
+            at_mobiles: Vec::new(),
+            at_all: false,
+        }
+    }
+
+    fn sign(secret: &str, timestamp: u128) -> String {
+        hmac_sha256_base64(
+            secret.as_bytes(),
+            format!("{}\n{}", timestamp, secret).as_bytes(),
+        )
+    }
+
+    fn get_body(&self, message: &Message) -> Value {
+        let title = self.title.as_deref().unwrap_or(DEFAULT_TITLE);
+        let color = if message.under_threshold {
+            "#FF0000"
+        } else {
+            "#008000"
+        };
+        let mut text = format!(
+            "### {}\n\n<font color=\"{}\">{}</font>",
+            title, color, message.text
+        );
+        for mobile in self.at_mobiles.iter() {
+            text.push_str(&format!(" @{}", mobile));
+        }
+
+        json!({
+            "msgtype": "markdown",
+            "markdown": {
+                "title": title,
+                "text": text,
+            },
+            "at": {
+                "atMobiles": self.at_mobiles,
+                "isAtAll": self.at_all,
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl Notify for DingTalk {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let client = crate::http::client();
+        let mut request = client.post(&self.url).json(&self.get_body(message));
+        if let Some(secret) = self.secret.as_ref() {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            request = request.query(&[
+                ("timestamp", timestamp.to_string()),
+                ("sign", Self::sign(secret, timestamp)),
+            ]);
+        }
+        let response = request.send().await.context(NetworkSnafu)?;
+
+        check_robot_response(response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(
+            DingTalk::sign("SECabc", 1700000000000),
+            "jcUpW0QmtKduN03n4JqQ0PBosVjqnM8gU7fIIvsDmCM="
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/robot/send")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("access_token".to_string(), "token".to_string()),
+                Matcher::Regex("timestamp=\\d+".to_string()),
+                Matcher::Regex("sign=".to_string()),
+            ]))
+            .match_body(Matcher::PartialJson(json!({
+                "msgtype": "markdown",
+                "at": { "atMobiles": ["13800000000"], "isAtAll": false },
+            })))
+            .with_status(200)
+            .with_body(r#"{"errcode":0,"errmsg":"ok"}"#)
+            .create_async()
+            .await;
+
+        let mut dingtalk = DingTalk::new(
+            &format!("{}/robot/send?access_token=token", server.url()),
+            Some("SECabc"),
+            None,
+        );
+        dingtalk.at_mobiles = vec!["13800000000".to_string()];
+        dingtalk
+            .send_message(&Message::new("cnh_cny", "低于预设值", true, 0.997))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+}
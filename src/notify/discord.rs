@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::*;
+
+/// A Discord channel webhook, see <https://discord.com/developers/docs/resources/webhook>.
+#[derive(Deserialize, Serialize)]
+pub struct Discord {
+    url: String,
+    title: Option<String>,
+    /// Overrides the default username of the webhook.
+    username: Option<String>,
+    avatar_url: Option<String>,
+}
+
+impl Debug for Discord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Discord")
+            .field("title", &self.title)
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+impl Discord {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, title: Option<&str>) -> Self {
+        Self {
+            url: url.to_string(),
+            title: title.map(|s| s.to_string()),
+            username: None,
+            avatar_url: None,
+        }
+    }
+
+    fn get_body(&self, message: &Message) -> Value {
+        // Red when falling below the threshold, green on recovery
+        let color = if message.under_threshold {
+            0xE74C3C
+        } else {
+            0x2ECC71
+        };
+
+        let mut body = json!({
+            "embeds": [{
+                "title": self.title.as_deref().unwrap_or(DEFAULT_TITLE),
+                "description": message.text,
+                "color": color,
+                "fields": [{
+                    "name": message.monitor,
+                    "value": format!("{:.5}", message.rate),
+                    "inline": true,
+                }],
+            }],
+        });
+        if let Some(username) = self.username.as_ref() {
+            body["username"] = json!(username);
+        }
+        if let Some(avatar_url) = self.avatar_url.as_ref() {
+            body["avatar_url"] = json!(avatar_url);
+        }
+
+        body
+    }
+}
+
+/// Reads the delay requested by a 429 response, preferring the body over the header.
+/// Discord puts the seconds to wait in `retry_after`, with a fraction.
+async fn get_retry_after(response: reqwest::Response) -> Option<Duration> {
+    let header = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<f64>().ok());
+    let body = response
+        .json::<Value>()
+        .await
+        .ok()
+        .and_then(|json| json.get("retry_after").and_then(Value::as_f64));
+
+    let seconds = body.or(header).filter(|s| !s.is_nan())?;
+    Some(Duration::try_from_secs_f64(seconds.max(0.0)).unwrap_or(Duration::MAX))
+}
+
+#[async_trait]
+impl Notify for Discord {
+    /// A rate limited request fails with the requested delay, which the retry policy honours.
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let response = crate::http::client()
+            .post(&self.url)
+            .json(&self.get_body(message))
+            .send()
+            .await
+            .context(NetworkSnafu)?;
+
+        let error = response.error_for_status_ref().err();
+        if let Some(source) = error.filter(|e| e.status() == Some(StatusCode::TOO_MANY_REQUESTS)) {
+            let retry_after = get_retry_after(response).await;
+            return Err(Error::HttpStatus {
+                source,
+                retry_after,
+            });
+        }
+
+        check_status(response)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message_retry_after() {
+        let mut server = mockito::Server::new_async().await;
+
+        let limited = server
+            .mock("POST", "/api/webhooks/1/token")
+            .with_status(429)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"message":"You are being rate limited.","retry_after":0.05,"global":false}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let accepted = server
+            .mock("POST", "/api/webhooks/1/token")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "embeds": [{ "description": "CNH/CNY recovered", "color": 0x2ECC71 }],
+            })))
+            .with_status(204)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let discord = Notifier::from(NotifyType::Discord(Discord::new(
+            &format!("{}/api/webhooks/1/token", server.url()),
+            None,
+        )));
+        let attempts = discord
+            .deliver(
+                &Message::new("cnh_cny", "CNH/CNY recovered", false, 0.999),
+                &RetryPolicy::default(),
+                30.0,
+            )
+            .await
+            .unwrap();
+        assert_eq!(attempts, 2);
+        limited.assert_async().await;
+        accepted.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_from_body() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_status(429)
+            .with_header("retry-after", "2")
+            .with_body(r#"{"retry_after":0.25}"#)
+            .create_async()
+            .await;
+
+        let discord = Discord::new(&server.url(), None);
+        let error = discord
+            .send_message(&Message::new("cnh_cny", "below", true, 0.997))
+            .await
+            .unwrap_err();
+        assert!(error.is_transient());
+        assert_eq!(error.retry_after(), Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn test_color_by_direction() {
+        let discord = Discord::new("http://example.com", None);
+        let below = discord.get_body(&Message::new("cnh_cny", "below", true, 0.997));
+        let above = discord.get_body(&Message::new("cnh_cny", "above", false, 0.999));
+        assert_eq!(below["embeds"][0]["color"], 0xE74C3C);
+        assert_eq!(above["embeds"][0]["color"], 0x2ECC71);
+    }
+}
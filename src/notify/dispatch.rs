@@ -0,0 +1,682 @@
+use std::{fmt::Display, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use serde::Serialize;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use super::{
+    outbox::Outbox,
+    retry::RetryPolicy,
+    routing::Rule,
+    schedule::{QuietAction, Schedule},
+    throttle::Throttle,
+    Error, Message, Notifier,
+};
+
+/// The outcome of delivering one alert to every notifier.
+#[derive(Debug, Serialize)]
+pub struct DeliveryReport {
+    pub monitor: String,
+    pub deliveries: Vec<Delivery>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Delivery {
+    /// The notifier key, see [`Notifier::key`].
+    pub notifier: String,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+    /// Wall time spent on the notifier, including retries and queued messages.
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", rename_all = "lowercase")]
+pub enum Outcome {
+    Delivered {
+        attempts: u32,
+    },
+    Failed {
+        error: String,
+        transient: bool,
+    },
+    /// Dropped by the notifier's rate limit.
+    RateLimited,
+    /// Dropped during a quiet window.
+    Suppressed,
+    /// Held back until the end of a quiet window.
+    Deferred,
+}
+
+impl From<Result<u32, Error>> for Outcome {
+    fn from(result: Result<u32, Error>) -> Self {
+        match result {
+            Ok(attempts) => Outcome::Delivered { attempts },
+            Err(e) => Outcome::Failed {
+                transient: e.is_transient(),
+                error: e.to_string(),
+            },
+        }
+    }
+}
+
+impl DeliveryReport {
+    pub fn delivered(&self) -> usize {
+        self.deliveries
+            .iter()
+            .filter(|delivery| matches!(delivery.outcome, Outcome::Delivered { .. }))
+            .count()
+    }
+
+    /// Logs a summary line, plus a line for every notifier that didn't get the message.
+    pub fn log(&self) {
+        for delivery in self.deliveries.iter() {
+            match &delivery.outcome {
+                Outcome::Failed { error, .. } => warn!(
+                    "Failed to send the message use {}: {}",
+                    delivery.notifier, error
+                ),
+                Outcome::RateLimited => info!("Rate limited the message to {}", delivery.notifier),
+                Outcome::Suppressed => info!("Suppressed the message to {}", delivery.notifier),
+                Outcome::Deferred => info!("Deferred the message to {}", delivery.notifier),
+                Outcome::Delivered { .. } => {}
+            }
+        }
+        info!("{}", self);
+    }
+}
+
+impl Display for DeliveryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Delivered the {} alert to {}/{} notifiers",
+            self.monitor,
+            self.delivered(),
+            self.deliveries.len()
+        )
+    }
+}
+
+/// Fans alerts out to the notifiers selected by the routing rules.
+pub struct Dispatcher<'a> {
+    pub notifiers: &'a [Notifier],
+    pub rules: &'a [Rule],
+    /// The default retry policy, see [`Notifier::retry`].
+    pub retry: &'a RetryPolicy,
+    /// The default timeout in seconds, see [`Notifier::timeout`].
+    pub timeout: f64,
+    pub outbox: Option<&'a Outbox>,
+    pub throttle: Throttle,
+}
+
+impl Dispatcher<'_> {
+    /// Delivers `message` to the routed notifiers concurrently, each bounded by its own timeout.
+    /// With an outbox, undelivered messages are kept and replayed first, so this is also
+    /// called without a message on every poll; notifiers with nothing to send are left out.
+    /// Alerts deferred by a quiet window are sent as a digest on the first call after it.
+    /// Identical alerts are dropped for `dedup_window` once one was delivered or deferred.
+    pub async fn dispatch(&self, message: Option<&Message>) -> Vec<Delivery> {
+        self.dispatch_at(message, Utc::now()).await
+    }
+
+    async fn dispatch_at(&self, message: Option<&Message>, now: DateTime<Utc>) -> Vec<Delivery> {
+        let message = message.filter(|message| {
+            let duplicate = self.throttle.is_duplicate(message);
+            if duplicate {
+                debug!("Dropped a duplicate alert: {}", message.text);
+            }
+            !duplicate
+        });
+        let rules = message
+            .map(|message| self.active_rules(message))
+            .unwrap_or_default();
+        let rules = &rules;
+
+        let tasks = self
+            .notifiers
+            .iter()
+            .enumerate()
+            .map(|(index, notifier)| async move {
+                let key = notifier.key(index);
+                let routing = rules
+                    .iter()
+                    .map(|(_, rule)| *rule)
+                    .filter(|rule| rule.includes(notifier))
+                    .collect::<Vec<_>>();
+                let message = message.filter(|_| self.rules.is_empty() || !routing.is_empty());
+
+                let mut skipped = None;
+                let mut messages = Vec::new();
+                // Whether `message` was delivered or deferred, which starts the rules' cooldown.
+                let mut reached = false;
+                if let Some(message) = message {
+                    match quiet_action(notifier, &routing, now) {
+                        None => messages.push(message.clone()),
+                        Some(QuietAction::Downgrade) => messages.push(message.downgrade()),
+                        Some(QuietAction::Defer) => {
+                            self.throttle.defer(&key, message.clone());
+                            skipped = Some(Outcome::Deferred);
+                            reached = true;
+                        }
+                        Some(QuietAction::Suppress) => skipped = Some(Outcome::Suppressed),
+                    }
+                }
+                let all_rules = self
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.includes(notifier))
+                    .collect::<Vec<_>>();
+                let mut deferred = Vec::new();
+                if quiet_action(notifier, &all_rules, now).is_none() {
+                    deferred = self.throttle.take_deferred(&key);
+                }
+
+                let limit = notifier
+                    .rate_limit
+                    .as_ref()
+                    .or(self.throttle.default_rate_limit());
+                let mut acquire = || {
+                    let allowed = limit.is_none_or(|limit| self.throttle.acquire(&key, limit));
+                    if !allowed {
+                        skipped = Some(Outcome::RateLimited);
+                    }
+                    allowed
+                };
+                let digest = Message::digest(&deferred).filter(|_| acquire());
+                messages.retain(|_| acquire());
+                let sending = !messages.is_empty();
+                if digest.is_none() {
+                    // Rate limited, kept for the next digest.
+                    self.throttle
+                        .restore_deferred(&key, std::mem::take(&mut deferred));
+                }
+                messages.splice(0..0, digest);
+
+                let start = Instant::now();
+                let result = self.deliver(notifier, &key, &messages, deferred).await;
+                reached |= sending && result.is_ok();
+                let outcome = match result {
+                    Ok(0) => skipped,
+                    result => Some(Outcome::from(result)),
+                };
+                let delivery = outcome.map(|outcome| Delivery {
+                    notifier: key,
+                    outcome,
+                    elapsed: start.elapsed(),
+                });
+                (delivery, reached)
+            });
+        let results = join_all(tasks).await;
+
+        if let Some(message) = message {
+            if results.iter().any(|(_, reached)| *reached) {
+                self.throttle.record_seen(message);
+            }
+        }
+        for (index, rule) in rules.iter() {
+            let fired = self
+                .notifiers
+                .iter()
+                .zip(results.iter())
+                .any(|(notifier, (_, reached))| *reached && rule.includes(notifier));
+            if fired {
+                self.throttle.start_cooldown(*index);
+            }
+        }
+        results
+            .into_iter()
+            .filter_map(|(delivery, _)| delivery)
+            .collect()
+    }
+
+    /// Delivers `message` to the named notifiers, or every notifier when `names` is empty,
+    /// regardless of the rules, throttling and quiet windows. Used for scheduled reports.
+    pub async fn send_to(&self, names: &[String], message: &Message) -> Vec<Delivery> {
+        let tasks = self
+            .notifiers
+            .iter()
+            .enumerate()
+            .filter(|(_, notifier)| {
+                names.is_empty()
+                    || notifier
+                        .name
+                        .as_ref()
+                        .is_some_and(|name| names.contains(name))
+            })
+            .map(|(index, notifier)| async move {
+                let start = Instant::now();
+                let result = notifier.deliver(message, self.retry, self.timeout).await;
+                Delivery {
+                    notifier: notifier.key(index),
+                    outcome: Outcome::from(result),
+                    elapsed: start.elapsed(),
+                }
+            });
+
+        join_all(tasks).await
+    }
+
+    /// Hands a polled rate to every notifier concurrently, see [`super::Notify::record_rate`].
+    pub async fn record_rate(&self, monitor: &str, rate: f64) {
+        let tasks = self
+            .notifiers
+            .iter()
+            .enumerate()
+            .map(|(index, notifier)| async move {
+                if let Err(e) = notifier
+                    .record_rate(monitor, rate, self.retry, self.timeout)
+                    .await
+                {
+                    warn!(
+                        "Failed to record the rate use {}: {}",
+                        notifier.key(index),
+                        e
+                    );
+                }
+            });
+        join_all(tasks).await;
+    }
+
+    /// Sends `messages` in order, through the outbox if any, which keeps them until delivered
+    /// and also replays its queue. Without an outbox, stops at the first failure and returns
+    /// `deferred`, the alerts summarised by the first message, to the queue if it wasn't sent.
+    /// Returns the total number of attempts.
+    async fn deliver(
+        &self,
+        notifier: &Notifier,
+        key: &str,
+        messages: &[Message],
+        deferred: Vec<Message>,
+    ) -> Result<u32, Error> {
+        if let Some(outbox) = self.outbox {
+            return outbox
+                .deliver(notifier, key, messages, self.retry, self.timeout)
+                .await;
+        }
+
+        let mut attempts = 0;
+        let mut deferred = Some(deferred);
+        for message in messages.iter() {
+            match notifier.deliver(message, self.retry, self.timeout).await {
+                Ok(count) => attempts += count,
+                Err(e) => {
+                    if let Some(deferred) = deferred {
+                        self.throttle.restore_deferred(key, deferred);
+                    }
+                    return Err(e);
+                }
+            }
+            deferred = None;
+        }
+        Ok(attempts)
+    }
+
+    /// The rules matching the severity of `message` and out of their cooldown, by index.
+    fn active_rules(&self, message: &Message) -> Vec<(usize, &Rule)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(|(index, rule)| {
+                rule.matches(message.severity) && self.throttle.cooled_down(*index, rule.cooldown)
+            })
+            .collect()
+    }
+}
+
+/// The notifier's own quiet window takes precedence; otherwise the notifier is quiet
+/// only when every rule routing to it is, with the least restrictive of their actions.
+fn quiet_action(notifier: &Notifier, rules: &[&Rule], now: DateTime<Utc>) -> Option<QuietAction> {
+    let quiet =
+        |schedule: Option<&Schedule>| schedule.and_then(|schedule| schedule.quiet_action(now));
+    if let Some(action) = quiet(notifier.schedule.as_ref()) {
+        return Some(action);
+    }
+    if rules.is_empty() {
+        return None;
+    }
+
+    rules
+        .iter()
+        .map(|rule| quiet(rule.schedule.as_ref()))
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tokio::net::TcpListener;
+
+    use crate::notify::{
+        mqtt::Mqtt,
+        schedule::Window,
+        throttle::RateLimit,
+        webhook::{Method, Webhook},
+        NotifyType, Severity,
+    };
+
+    use super::*;
+
+    fn webhook(url: &str) -> Notifier {
+        Notifier::from(NotifyType::Webhook(Webhook::new(
+            url,
+            HashMap::new(),
+            None,
+            Method::Post,
+        )))
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_with_timeout() {
+        let mut server = mockito::Server::new_async().await;
+        let fast = server
+            .mock("POST", "/fast")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+        // Accepts the connection but never answers.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut slow = webhook(&format!("http://{}/slow", listener.local_addr().unwrap()));
+        slow.timeout = Some(0.2);
+        let notifiers = vec![webhook(&format!("{}/fast", server.url())), slow];
+        let retry = RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        };
+
+        let dispatcher = Dispatcher {
+            notifiers: &notifiers,
+            rules: &[],
+            retry: &retry,
+            timeout: 30.0,
+            outbox: None,
+            throttle: Throttle::default(),
+        };
+
+        let start = Instant::now();
+        let deliveries = dispatcher
+            .dispatch(Some(&Message::new("cnh_cny", "below", true, 0.997)))
+            .await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+        fast.assert_async().await;
+
+        let report = DeliveryReport {
+            monitor: "cnh_cny".to_string(),
+            deliveries,
+        };
+        assert_eq!(report.delivered(), 1);
+        assert_eq!(report.deliveries[0].notifier, "0-webhook");
+        assert!(matches!(
+            report.deliveries[0].outcome,
+            Outcome::Delivered { attempts: 1 }
+        ));
+        assert!(matches!(
+            report.deliveries[1].outcome,
+            Outcome::Failed {
+                transient: true,
+                ..
+            }
+        ));
+        assert_eq!(
+            report.to_string(),
+            "Delivered the cnh_cny alert to 1/2 notifiers"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_without_message() {
+        let notifiers = vec![webhook("http://127.0.0.1:1/hook")];
+        let dispatcher = Dispatcher {
+            notifiers: &notifiers,
+            rules: &[],
+            retry: &RetryPolicy::default(),
+            timeout: 30.0,
+            outbox: None,
+            throttle: Throttle::default(),
+        };
+        assert!(dispatcher.dispatch(None).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_record_rate_with_timeout() {
+        // A broker that accepts the connection but never acknowledges.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut mqtt = Notifier::from(NotifyType::Mqtt(Mqtt::new(
+            "127.0.0.1",
+            Some(port),
+            "forex/alert",
+            Some("forex/rate"),
+        )));
+        mqtt.timeout = Some(0.2);
+        let notifiers = vec![mqtt];
+        let dispatcher = Dispatcher {
+            notifiers: &notifiers,
+            rules: &[],
+            retry: &RetryPolicy {
+                max_attempts: 1,
+                ..Default::default()
+            },
+            timeout: 30.0,
+            outbox: None,
+            throttle: Throttle::default(),
+        };
+
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            dispatcher.record_rate("cnh_cny", 0.999),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_routing_and_throttle() {
+        let mut server = mockito::Server::new_async().await;
+        let ntfy_mock = server
+            .mock("POST", "/ntfy")
+            .with_status(200)
+            .expect(2)
+            .create_async()
+            .await;
+        let telegram_mock = server
+            .mock("POST", "/telegram")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut ntfy = webhook(&format!("{}/ntfy", server.url()));
+        ntfy.name = Some("ntfy".to_string());
+        ntfy.rate_limit = Some(RateLimit {
+            capacity: 2.0,
+            refill_interval: 3600.0,
+        });
+        let mut telegram = webhook(&format!("{}/telegram", server.url()));
+        telegram.name = Some("telegram".to_string());
+        let notifiers = vec![ntfy, telegram];
+        let rules = vec![
+            Rule {
+                severity: Severity::Info,
+                notifiers: vec!["ntfy".to_string()],
+                cooldown: None,
+                schedule: None,
+            },
+            Rule {
+                severity: Severity::Critical,
+                notifiers: vec!["telegram".to_string()],
+                cooldown: Some(600),
+                schedule: None,
+            },
+        ];
+        let dispatcher = Dispatcher {
+            notifiers: &notifiers,
+            rules: &rules,
+            retry: &RetryPolicy::default(),
+            timeout: 30.0,
+            outbox: None,
+            throttle: Throttle::default(),
+        };
+        let critical = Message::new("cnh_cny", "critical", true, 0.985)
+            .with_level("critical", Severity::Critical);
+
+        let deliveries = dispatcher.dispatch(Some(&critical)).await;
+        assert_eq!(deliveries.len(), 2);
+
+        // The telegram rule is cooling down.
+        let deliveries = dispatcher.dispatch(Some(&critical)).await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].notifier, "ntfy");
+
+        // ntfy ran out of tokens.
+        let deliveries = dispatcher.dispatch(Some(&critical)).await;
+        assert_eq!(deliveries.len(), 1);
+        assert!(matches!(deliveries[0].outcome, Outcome::RateLimited));
+
+        ntfy_mock.assert_async().await;
+        telegram_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_cooldown_and_dedup_after_suppressed_alert() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/telegram")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut telegram = webhook(&format!("{}/telegram", server.url()));
+        telegram.name = Some("telegram".to_string());
+        telegram.schedule = Some(Schedule {
+            timezone: "Asia/Shanghai".parse().unwrap(),
+            windows: vec![Window {
+                days: Vec::new(),
+                start: "23:00".parse().unwrap(),
+                end: "07:00".parse().unwrap(),
+            }],
+            action: QuietAction::Suppress,
+        });
+        let notifiers = vec![telegram];
+        let rules = vec![Rule {
+            severity: Severity::Critical,
+            notifiers: vec!["telegram".to_string()],
+            cooldown: Some(600),
+            schedule: None,
+        }];
+        let dispatcher = Dispatcher {
+            notifiers: &notifiers,
+            rules: &rules,
+            retry: &RetryPolicy::default(),
+            timeout: 30.0,
+            outbox: None,
+            throttle: Throttle::new(Some(3600), None),
+        };
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap().to_utc();
+        let critical = Message::new("cnh_cny", "critical", true, 0.985)
+            .with_level("critical", Severity::Critical);
+
+        // 23:30 in Shanghai, suppressed without starting the cooldown or the dedup window.
+        let deliveries = dispatcher
+            .dispatch_at(Some(&critical), at("2024-09-11T15:30:00Z"))
+            .await;
+        assert!(matches!(deliveries[0].outcome, Outcome::Suppressed));
+
+        // 07:10, the next alert still goes out.
+        let deliveries = dispatcher
+            .dispatch_at(Some(&critical), at("2024-09-11T23:10:00Z"))
+            .await;
+        assert!(matches!(
+            deliveries[0].outcome,
+            Outcome::Delivered { attempts: 1 }
+        ));
+        mock.assert_async().await;
+
+        let deliveries = dispatcher
+            .dispatch_at(Some(&critical), at("2024-09-11T23:20:00Z"))
+            .await;
+        assert!(deliveries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_quiet_hours() {
+        let mut server = mockito::Server::new_async().await;
+        let down = server
+            .mock("POST", "/hook")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let digest = server
+            .mock("POST", "/hook")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut notifier = webhook(&format!("{}/hook", server.url()));
+        notifier.schedule = Some(Schedule {
+            timezone: "Asia/Shanghai".parse().unwrap(),
+            windows: vec![Window {
+                days: Vec::new(),
+                start: "23:00".parse().unwrap(),
+                end: "07:00".parse().unwrap(),
+            }],
+            action: QuietAction::Defer,
+        });
+        let notifiers = vec![notifier];
+        let dispatcher = Dispatcher {
+            notifiers: &notifiers,
+            rules: &[],
+            retry: &RetryPolicy {
+                max_attempts: 1,
+                ..Default::default()
+            },
+            timeout: 30.0,
+            outbox: None,
+            throttle: Throttle::default(),
+        };
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap().to_utc();
+
+        // 23:30 and 02:00 in Shanghai.
+        let deliveries = dispatcher
+            .dispatch_at(
+                Some(&Message::new("cnh_cny", "below", true, 0.997)),
+                at("2024-09-11T15:30:00Z"),
+            )
+            .await;
+        assert!(matches!(deliveries[0].outcome, Outcome::Deferred));
+        dispatcher
+            .dispatch_at(
+                Some(&Message::new("cnh_cny", "above", false, 0.999)),
+                at("2024-09-11T18:00:00Z"),
+            )
+            .await;
+        assert!(dispatcher
+            .dispatch_at(None, at("2024-09-11T22:00:00Z"))
+            .await
+            .is_empty());
+
+        // 07:10, the window is over but the first digest fails and is kept.
+        let deliveries = dispatcher
+            .dispatch_at(None, at("2024-09-11T23:10:00Z"))
+            .await;
+        assert!(matches!(deliveries[0].outcome, Outcome::Failed { .. }));
+        down.assert_async().await;
+        let deliveries = dispatcher
+            .dispatch_at(None, at("2024-09-11T23:13:00Z"))
+            .await;
+        assert!(matches!(
+            deliveries[0].outcome,
+            Outcome::Delivered { attempts: 1 }
+        ));
+        digest.assert_async().await;
+    }
+}
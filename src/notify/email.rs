@@ -0,0 +1,242 @@
+use lettre::{
+    message::{header::ContentType, Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+};
+use snafu::ResultExt;
+
+use super::*;
+
+#[derive(Deserialize, Serialize)]
+pub struct Email {
+    host: String,
+    port: Option<u16>,
+    #[serde(default)]
+    security: Security,
+    username: Option<String>,
+    password: Option<String>,
+    from: String,
+    to: Vec<String>,
+    /// Subject template, see [`Message::render`] for the supported placeholders.
+    subject: String,
+    /// Plain text body template, defaults to the message text.
+    template: Option<String>,
+    /// HTML body template, sent alongside the plain text body when set.
+    html_template: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy)]
+pub enum Security {
+    /// Plain SMTP, only suitable for a relay on a trusted network.
+    None,
+    #[default]
+    StartTls,
+    /// Implicit TLS, usually on port 465.
+    Tls,
+}
+
+impl Debug for Email {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Email")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("security", &self.security)
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish()
+    }
+}
+
+impl Email {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(host: &str, port: Option<u16>, security: Security, from: &str, to: &[&str]) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            security,
+            username: None,
+            password: None,
+            from: from.to_string(),
+            to: to.iter().map(|s| s.to_string()).collect(),
+            subject: "CNH/CNY {monitor}".to_string(),
+            template: None,
+            html_template: None,
+        }
+    }
+
+    fn get_mailboxes(&self) -> Result<(Mailbox, Vec<Mailbox>), Error> {
+        let parse = |address: &String| {
+            address.parse::<Mailbox>().context(AddressSnafu {
+                address: address.to_string(),
+            })
+        };
+
+        let from = parse(&self.from)?;
+        let to = self.to.iter().map(parse).collect::<Result<Vec<_>, _>>()?;
+        if to.is_empty() {
+            return NoRecipientSnafu.fail();
+        }
+
+        Ok((from, to))
+    }
+
+    fn get_transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, Error> {
+        let mut builder = match self.security {
+            Security::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host),
+            Security::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)
+                .context(SmtpSnafu)?,
+            Security::Tls => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host).context(SmtpSnafu)?
+            }
+        };
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (self.username.as_ref(), self.password.as_ref()) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(builder.build())
+    }
+
+    fn build_email(&self, message: &Message) -> Result<lettre::Message, Error> {
+        let (from, to) = self.get_mailboxes()?;
+        let mut builder = lettre::Message::builder()
+            .from(from)
+            .subject(message.render(&self.subject));
+        for mailbox in to {
+            builder = builder.to(mailbox);
+        }
+
+        let plain = match self.template.as_ref() {
+            Some(template) => message.render(template),
+            None => message.text.clone(),
+        };
+        let email = match self.html_template.as_ref() {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(
+                plain,
+                message.render(html),
+            )),
+            None => builder.header(ContentType::TEXT_PLAIN).body(plain),
+        };
+
+        email.context(BuildEmailSnafu)
+    }
+}
+
+#[async_trait]
+impl Notify for Email {
+    fn validate(&self) -> Result<(), Error> {
+        self.get_mailboxes()?;
+        self.get_transport()?;
+        Ok(())
+    }
+
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let email = self.build_email(message)?;
+        self.get_transport()?.send(email).await.context(SmtpSnafu)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Accepts a single SMTP session and returns everything the client sent.
+    async fn smtp_sink(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let mut transcript = String::new();
+        let mut in_data = false;
+
+        writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            transcript.push_str(&line);
+
+            let reply: &[u8] = if in_data {
+                if line != ".\r\n" {
+                    continue;
+                }
+                in_data = false;
+                b"250 OK\r\n"
+            } else if line.starts_with("EHLO") {
+                b"250 localhost\r\n"
+            } else if line.starts_with("DATA") {
+                in_data = true;
+                b"354 End data with <CR><LF>.<CR><LF>\r\n"
+            } else if line.starts_with("QUIT") {
+                writer.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 OK\r\n"
+            };
+            writer.write_all(reply).await.unwrap();
+        }
+
+        transcript
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        let mut email = Email::new(
+            "127.0.0.1",
+            Some(port),
+            Security::None,
+            "Forex Notify <bot@example.com>",
+            &["alice@example.com", "bob@example.com"],
+        );
+        email.html_template = Some("<b>{text}</b>".to_string());
+        email.validate().unwrap();
+
+        email
+            .send_message(&Message::new("cnh_cny", "CNH/CNY below", true, 0.997))
+            .await
+            .unwrap();
+
+        let transcript = sink.await.unwrap();
+        assert!(transcript.contains("MAIL FROM:<bot@example.com>"));
+        assert!(transcript.contains("RCPT TO:<alice@example.com>"));
+        assert!(transcript.contains("RCPT TO:<bob@example.com>"));
+        assert!(transcript.contains("Subject: CNH/CNY cnh_cny"));
+        assert!(transcript.contains("CNH/CNY below"));
+        assert!(transcript.contains("<b>CNH/CNY below</b>"));
+    }
+
+    #[test]
+    fn test_validate_invalid_address() {
+        let email = Email::new(
+            "127.0.0.1",
+            None,
+            Security::StartTls,
+            "not an address",
+            &["alice@example.com"],
+        );
+        assert!(matches!(email.validate(), Err(Error::Address { .. })));
+
+        let email = Email::new(
+            "127.0.0.1",
+            None,
+            Security::StartTls,
+            "bot@example.com",
+            &[],
+        );
+        assert!(matches!(email.validate(), Err(Error::NoRecipient)));
+    }
+}
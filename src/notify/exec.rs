@@ -0,0 +1,184 @@
+use std::{collections::HashMap, process::Stdio};
+
+use snafu::ResultExt;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use super::*;
+
+/// Runs a program for every alert. Arguments and environment values are templates,
+/// see [`Message::render`], and the message can also be written to stdin as JSON.
+/// The program is killed once the notifier's `timeout` is over.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Exec {
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    stdin: bool,
+    /// Exit codes treated as success.
+    #[serde(default = "default_success_codes")]
+    success_codes: Vec<i32>,
+}
+
+fn default_success_codes() -> Vec<i32> {
+    vec![0]
+}
+
+impl Exec {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+            env: HashMap::new(),
+            stdin: false,
+            success_codes: default_success_codes(),
+        }
+    }
+
+    /// Dropping the future, e.g. on timeout, kills the program.
+    async fn run(&self, message: &Message) -> Result<std::process::Output, Error> {
+        let mut command = Command::new(&self.program);
+        command
+            .args(self.args.iter().map(|arg| message.render(arg)))
+            .envs(
+                self.env
+                    .iter()
+                    .map(|(key, value)| (key, message.render(value))),
+            )
+            .stdin(if self.stdin {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = command.spawn().context(SpawnSnafu {
+            program: self.program.clone(),
+        })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            let json = serde_json::to_vec(message).expect("Message is always serializable");
+            // A program that exits without reading stdin is judged by its exit status.
+            match stdin.write_all(&json).await {
+                Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => {
+                    return Err(e).context(SpawnSnafu {
+                        program: self.program.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        child.wait_with_output().await.context(SpawnSnafu {
+            program: self.program.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Notify for Exec {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let output = self.run(message).await?;
+
+        match output.status.code() {
+            Some(code) if self.success_codes.contains(&code) => Ok(()),
+            _ => ExitStatusSnafu {
+                program: self.program.clone(),
+                status: output.status.to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            }
+            .fail(),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut exec = Exec::new(
+            "sh",
+            &[
+                "-c",
+                r#"read -r json; test "$1" = cnh_cny && test "$FX_RATE" = 0.997 && echo "$json" | grep -q '"under_threshold":true'"#,
+                "sh",
+                "{monitor}",
+            ],
+        );
+        exec.env = HashMap::from([("FX_RATE".to_string(), "{rate}".to_string())]);
+        exec.stdin = true;
+
+        exec.send_message(&Message::new("cnh_cny", "below", true, 0.997))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stdin_not_read() {
+        let mut exec = Exec::new("sh", &["-c", "exit 0"]);
+        exec.stdin = true;
+        let message = Message::new("cnh_cny", &"below".repeat(100_000), true, 0.997);
+        exec.send_message(&message).await.unwrap();
+
+        let mut exec = Exec::new("sh", &["-c", "exit 3"]);
+        exec.stdin = true;
+        let error = exec.send_message(&message).await.unwrap_err();
+        assert!(matches!(error, Error::ExitStatus { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_exit_status() {
+        let exec = Exec::new("sh", &["-c", "echo failed >&2; exit 3"]);
+        let error = exec
+            .send_message(&Message::new("cnh_cny", "below", true, 0.997))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::ExitStatus { ref stderr, .. } if stderr == "failed"));
+
+        let mut exec = Exec::new("sh", &["-c", "exit 3"]);
+        exec.success_codes = vec![0, 3];
+        exec.send_message(&Message::new("cnh_cny", "below", true, 0.997))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let mut notifier = Notifier::from(NotifyType::Exec(Exec::new("sleep", &["5"])));
+        notifier.timeout = Some(0.5);
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        };
+        let error = notifier
+            .deliver(
+                &Message::new("cnh_cny", "below", true, 0.997),
+                &policy,
+                30.0,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Timeout { .. }));
+    }
+
+    #[test]
+    fn test_deserialize_timeout() {
+        let notifier: Notifier = toml::from_str(
+            r#"
+            type = "Exec"
+            program = "/usr/local/bin/order"
+            timeout = 60
+            "#,
+        )
+        .unwrap();
+        assert_eq!(notifier.timeout, Some(60.0));
+        assert!(matches!(notifier.kind, NotifyType::Exec(_)));
+    }
+}
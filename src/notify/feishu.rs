@@ -0,0 +1,145 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::*;
+
+/// A Feishu/Lark custom bot, see <https://open.feishu.cn/document/client-docs/bot-v3/add-custom-bot>.
+#[derive(Deserialize, Serialize)]
+pub struct Feishu {
+    url: String,
+    /// The signing secret, required when the bot has signature verification enabled.
+    secret: Option<String>,
+    title: Option<String>,
+}
+
+impl Debug for Feishu {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Feishu")
+            .field("title", &self.title)
+            .finish()
+    }
+}
+
+impl Feishu {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, secret: Option<&str>, title: Option<&str>) -> Self {
+        Self {
+            url: url.to_string(),
+            secret: secret.map(|s| s.to_string()),
+            title: title.map(|s| s.to_string()),
+        }
+    }
+
+    fn sign(secret: &str, timestamp: u64) -> String {
+        hmac_sha256_base64(format!("{}\n{}", timestamp, secret).as_bytes(), b"")
+    }
+
+    fn get_body(&self, message: &Message, timestamp: u64) -> Value {
+        let template = if message.under_threshold {
+            "red"
+        } else {
+            "green"
+        };
+        let mut body = json!({
+            "msg_type": "interactive",
+            "card": {
+                "header": {
+                    "title": {
+                        "tag": "plain_text",
+                        "content": self.title.as_deref().unwrap_or(DEFAULT_TITLE),
+                    },
+                    "template": template,
+                },
+                "elements": [{
+                    "tag": "div",
+                    "text": {
+                        "tag": "lark_md",
+                        "content": message.text,
+                    },
+                }],
+            },
+        });
+        if let Some(secret) = self.secret.as_ref() {
+            body["timestamp"] = json!(timestamp.to_string());
+            body["sign"] = json!(Self::sign(secret, timestamp));
+        }
+
+        body
+    }
+}
+
+#[async_trait]
+impl Notify for Feishu {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let client = crate::http::client();
+        let response = client
+            .post(&self.url)
+            .json(&self.get_body(message, timestamp))
+            .send()
+            .await
+            .context(NetworkSnafu)?;
+
+        check_robot_response(response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(
+            Feishu::sign("SECabc", 1700000000),
+            "XprR1de+0SSBnwWyU/4k6x2TL+Q2SJlM5NNEdAv7MWg="
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/hook")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::PartialJson(json!({
+                    "msg_type": "interactive",
+                    "card": { "header": { "template": "red" } },
+                })),
+                mockito::Matcher::Regex("\"sign\":".to_string()),
+            ]))
+            .with_status(200)
+            .with_body(r#"{"code":0,"msg":"success"}"#)
+            .create_async()
+            .await;
+        let rejected = server
+            .mock("POST", "/rejected")
+            .with_status(200)
+            .with_body(r#"{"code":19021,"msg":"sign match fail or timestamp is not within one hour from current time"}"#)
+            .create_async()
+            .await;
+
+        let feishu = Feishu::new(&format!("{}/hook", server.url()), Some("SECabc"), None);
+        feishu
+            .send_message(&Message::new("cnh_cny", "低于预设值", true, 0.997))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+
+        let feishu = Feishu::new(&format!("{}/rejected", server.url()), None, None);
+        let error = feishu
+            .send_message(&Message::new("cnh_cny", "低于预设值", true, 0.997))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Rejected { .. }));
+        rejected.assert_async().await;
+    }
+}
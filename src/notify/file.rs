@@ -0,0 +1,166 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::json;
+use snafu::ResultExt;
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+};
+
+use super::*;
+
+/// Appends every alert to a JSON Lines file, rotating it to `path.1`, `path.2`, ... when full.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct File {
+    path: PathBuf,
+    /// Size in bytes after which the file is rotated.
+    #[serde(default = "default_max_size")]
+    max_size: u64,
+    /// Number of rotated files to keep.
+    #[serde(default = "default_max_files")]
+    max_files: usize,
+}
+
+fn default_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+impl File {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(path: &Path, max_size: u64, max_files: usize) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            max_size,
+            max_files,
+        }
+    }
+
+    async fn rotate(&self) -> std::io::Result<()> {
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path).await;
+        }
+        for index in (1..self.max_files).rev() {
+            let from = rotated_path(&self.path, index);
+            if fs::try_exists(&from).await? {
+                fs::rename(from, rotated_path(&self.path, index + 1)).await?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1)).await
+    }
+
+    async fn append(&self, line: &str) -> std::io::Result<()> {
+        let size = fs::metadata(&self.path).await.map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.max_size {
+            self.rotate().await?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await
+    }
+}
+
+#[async_trait]
+impl Notify for File {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let mut line = json!({
+            "timestamp": timestamp,
+            "monitor": message.monitor,
+            "text": message.text,
+            "under_threshold": message.under_threshold,
+            "rate": message.rate,
+        })
+        .to_string();
+        line.push('\n');
+
+        self.append(&line).await.context(IoSnafu {
+            path: self.path.display().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let dir = temp_dir();
+        let path = dir.join("alerts.jsonl");
+        let file = File::new(&path, default_max_size(), default_max_files());
+
+        file.send_message(&Message::new("cnh_cny", "below", true, 0.997))
+            .await
+            .unwrap();
+        file.send_message(&Message::new("cnh_cny", "above", false, 0.999))
+            .await
+            .unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["under_threshold"], true);
+        assert_eq!(lines[1]["text"], "above");
+        assert!(lines[1]["timestamp"].as_u64().unwrap() > 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rotate() {
+        let dir = temp_dir();
+        let path = dir.join("alerts.jsonl");
+        // Small enough that every message triggers a rotation
+        let file = File::new(&path, 10, 2);
+
+        for text in ["first", "second", "third", "fourth"] {
+            file.send_message(&Message::new("cnh_cny", text, true, 0.997))
+                .await
+                .unwrap();
+        }
+
+        assert!(fs::read_to_string(&path).unwrap().contains("fourth"));
+        assert!(fs::read_to_string(rotated_path(&path, 1))
+            .unwrap()
+            .contains("third"));
+        assert!(fs::read_to_string(rotated_path(&path, 2))
+            .unwrap()
+            .contains("second"));
+        assert!(!rotated_path(&path, 3).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::*;
+
+/// A self-hosted Gotify server, see <https://gotify.net/docs/pushmsg>.
+#[derive(Deserialize, Serialize)]
+pub struct Gotify {
+    url: String,
+    /// The application token.
+    token: String,
+    title: Option<String>,
+    priority: Option<u8>,
+}
+
+impl Debug for Gotify {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Gotify")
+            .field("url", &self.url)
+            .field("title", &self.title)
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
+
+impl Gotify {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, token: &str, priority: Option<u8>) -> Self {
+        Self {
+            url: url.to_string(),
+            token: token.to_string(),
+            title: None,
+            priority,
+        }
+    }
+}
+
+#[async_trait]
+impl Notify for Gotify {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let mut body = json!({
+            "title": self.title.as_deref().unwrap_or(DEFAULT_TITLE),
+            "message": message.text,
+        });
+        if let Some(priority) = self.priority {
+            body["priority"] = json!(priority);
+        }
+
+        let client = crate::http::client();
+        let response = client
+            .post(format!("{}/message", self.url.trim_end_matches('/')))
+            .header("X-Gotify-Key", &self.token)
+            .json(&body)
+            .send()
+            .await
+            .context(NetworkSnafu)?;
+
+        check_status(response)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/message")
+            .match_header("x-gotify-key", "token")
+            .match_body(mockito::Matcher::Json(json!({
+                "title": DEFAULT_TITLE,
+                "message": "高于预设值",
+                "priority": 8,
+            })))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let gotify = Gotify::new(&server.url(), "token", Some(8));
+        gotify
+            .send_message(&Message::new("cnh_cny", "高于预设值", false, 0.999))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,108 @@
+use snafu::ResultExt;
+
+use super::*;
+
+/// Sends alerts to systemd-journald with structured fields, using its native protocol.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Journald {
+    #[serde(default = "default_socket")]
+    socket: String,
+    #[serde(default = "default_identifier")]
+    identifier: String,
+}
+
+fn default_socket() -> String {
+    "/run/systemd/journal/socket".to_string()
+}
+
+fn default_identifier() -> String {
+    "forex_notify".to_string()
+}
+
+/// Appends a field, switching to the length prefixed form for values containing newlines.
+fn push_field(buf: &mut Vec<u8>, key: &str, value: &str) {
+    buf.extend_from_slice(key.as_bytes());
+    if value.contains('\n') {
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(b'\n');
+}
+
+impl Journald {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(socket: &str) -> Self {
+        Self {
+            socket: socket.to_string(),
+            identifier: default_identifier(),
+        }
+    }
+
+    fn format(&self, message: &Message) -> Vec<u8> {
+        // warning when falling below the threshold, notice on recovery
+        let priority = if message.under_threshold { "4" } else { "5" };
+
+        let mut buf = Vec::new();
+        push_field(&mut buf, "MESSAGE", &message.text);
+        push_field(&mut buf, "PRIORITY", priority);
+        push_field(&mut buf, "SYSLOG_IDENTIFIER", &self.identifier);
+        push_field(&mut buf, "FOREX_MONITOR", &message.monitor);
+        push_field(&mut buf, "FOREX_RATE", &message.rate.to_string());
+        push_field(
+            &mut buf,
+            "FOREX_UNDER_THRESHOLD",
+            &message.under_threshold.to_string(),
+        );
+        buf
+    }
+}
+
+#[async_trait]
+impl Notify for Journald {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        send_datagram(&self.socket, &self.format(message))
+            .await
+            .context(IoSnafu {
+                path: self.socket.clone(),
+            })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use tokio::net::UnixDatagram;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let path = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        let journald = Journald::new(path.to_str().unwrap());
+        journald
+            .send_message(&Message::new("cnh_cny", "line one\nline two", false, 0.999))
+            .await
+            .unwrap();
+
+        let mut buf = vec![0; 1024];
+        let len = socket.recv(&mut buf).await.unwrap();
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&17u64.to_le_bytes());
+        expected.extend_from_slice(
+            b"line one\nline two\n\
+              PRIORITY=5\n\
+              SYSLOG_IDENTIFIER=forex_notify\n\
+              FOREX_MONITOR=cnh_cny\n\
+              FOREX_RATE=0.999\n\
+              FOREX_UNDER_THRESHOLD=false\n",
+        );
+        assert_eq!(&buf[..len], expected.as_slice());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,12 +1,43 @@
 use async_trait::async_trait;
+use base64::Engine;
 use enum_dispatch::enum_dispatch;
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
-use snafu::Snafu;
-use std::fmt::Debug;
+use serde_json::Value;
+use sha2::Sha256;
+use snafu::{ResultExt, Snafu};
+use std::{fmt::Debug, time::Duration};
 
+use crate::http::HttpConfig;
+use retry::RetryPolicy;
+use schedule::Schedule;
+use throttle::RateLimit;
+
+pub mod bark;
+pub mod dingtalk;
+pub mod discord;
+pub mod dispatch;
+pub mod email;
+pub mod exec;
+pub mod feishu;
+pub mod file;
+pub mod gotify;
+pub mod journald;
+pub mod mqtt;
 pub mod ntfy;
+pub mod outbox;
+pub mod pushover;
+pub mod pushplus;
+pub mod retry;
+pub mod routing;
+pub mod schedule;
+pub mod serverchan;
+pub mod slack;
+pub mod syslog;
 pub mod telegram;
+pub mod throttle;
 pub mod webhook;
+pub mod wecom;
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(tag = "type")]
@@ -15,12 +46,325 @@ pub enum NotifyType {
     Telegram(telegram::Telegram),
     Ntfy(ntfy::Ntfy),
     Webhook(webhook::Webhook),
+    Email(email::Email),
+    Feishu(feishu::Feishu),
+    DingTalk(dingtalk::DingTalk),
+    WeCom(wecom::WeCom),
+    Slack(slack::Slack),
+    Discord(discord::Discord),
+    Bark(bark::Bark),
+    Gotify(gotify::Gotify),
+    Pushover(pushover::Pushover),
+    ServerChan(serverchan::ServerChan),
+    PushPlus(pushplus::PushPlus),
+    Mqtt(mqtt::Mqtt),
+    Exec(exec::Exec),
+    File(file::File),
+    Syslog(syslog::Syslog),
+    Journald(journald::Journald),
+}
+
+/// An entry of `[[notifiers]]`: the notifier itself plus the delivery options shared by every kind.
+#[derive(Deserialize, Serialize)]
+pub struct Notifier {
+    /// Referenced by `[[rules]]`, also used as the key of the outbox queue.
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub kind: NotifyType,
+    /// Overrides the global `[retry]` policy.
+    pub retry: Option<RetryPolicy>,
+    /// Seconds allowed for each delivery attempt, overrides the global `timeout`.
+    pub timeout: Option<f64>,
+    /// Overrides the global `[rate_limit]`.
+    pub rate_limit: Option<RateLimit>,
+    /// Quiet windows of the notifier, taking precedence over those of the rules.
+    pub schedule: Option<Schedule>,
+    /// Overrides fields of the global `[http]` section for this notifier.
+    pub http: Option<HttpConfig>,
+    /// Built from `http` when the settings are loaded.
+    #[serde(skip)]
+    pub client: Option<reqwest::Client>,
+}
+
+impl Debug for Notifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl NotifyType {
+    /// The lowercase `type` of the notifier.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            NotifyType::Telegram(_) => "telegram",
+            NotifyType::Ntfy(_) => "ntfy",
+            NotifyType::Webhook(_) => "webhook",
+            NotifyType::Email(_) => "email",
+            NotifyType::Feishu(_) => "feishu",
+            NotifyType::DingTalk(_) => "dingtalk",
+            NotifyType::WeCom(_) => "wecom",
+            NotifyType::Slack(_) => "slack",
+            NotifyType::Discord(_) => "discord",
+            NotifyType::Bark(_) => "bark",
+            NotifyType::Gotify(_) => "gotify",
+            NotifyType::Pushover(_) => "pushover",
+            NotifyType::ServerChan(_) => "serverchan",
+            NotifyType::PushPlus(_) => "pushplus",
+            NotifyType::Mqtt(_) => "mqtt",
+            NotifyType::Exec(_) => "exec",
+            NotifyType::File(_) => "file",
+            NotifyType::Syslog(_) => "syslog",
+            NotifyType::Journald(_) => "journald",
+        }
+    }
+}
+
+impl From<NotifyType> for Notifier {
+    fn from(kind: NotifyType) -> Self {
+        Self {
+            name: None,
+            kind,
+            retry: None,
+            timeout: None,
+            rate_limit: None,
+            schedule: None,
+            http: None,
+            client: None,
+        }
+    }
+}
+
+impl Notifier {
+    /// Identifies the notifier in logs and the outbox queue file names: its name,
+    /// or its position and type when unnamed, which the outbox doesn't allow.
+    pub fn key(&self, index: usize) -> String {
+        match self.name.as_ref() {
+            Some(name) => name.clone(),
+            None => format!("{}-{}", index, self.kind.kind_name()),
+        }
+    }
+
+    /// Sends the message, retrying transient failures according to the notifier's own
+    /// retry policy or `default_retry`. Each attempt is abandoned after the notifier's
+    /// own timeout or `default_timeout` seconds. Returns the number of attempts made.
+    pub async fn deliver(
+        &self,
+        message: &Message,
+        default_retry: &RetryPolicy,
+        default_timeout: f64,
+    ) -> Result<u32, Error> {
+        self.attempt(default_retry, default_timeout, || {
+            self.kind.send_message(message)
+        })
+        .await
+    }
+
+    /// Hands a polled rate to the notifier, with the same retries and timeout as [`Self::deliver`].
+    pub async fn record_rate(
+        &self,
+        monitor: &str,
+        rate: f64,
+        default_retry: &RetryPolicy,
+        default_timeout: f64,
+    ) -> Result<u32, Error> {
+        self.attempt(default_retry, default_timeout, || {
+            self.kind.record_rate(monitor, rate)
+        })
+        .await
+    }
+
+    async fn attempt<F, Fut>(
+        &self,
+        default_retry: &RetryPolicy,
+        default_timeout: f64,
+        send: F,
+    ) -> Result<u32, Error>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<(), Error>>,
+    {
+        let policy = self.retry.as_ref().unwrap_or(default_retry);
+        let timeout = self.timeout.unwrap_or(default_timeout);
+        let run = policy.run(|| {
+            let attempt = send();
+            async move {
+                tokio::time::timeout(Duration::from_secs_f64(timeout), attempt)
+                    .await
+                    .unwrap_or_else(|_| TimeoutSnafu { timeout }.fail())
+            }
+        });
+        match self.client.as_ref() {
+            Some(client) => crate::http::scope(client.clone(), run).await,
+            None => run.await,
+        }
+    }
 }
 
 #[async_trait]
 #[enum_dispatch]
 pub trait Notify {
-    async fn send_message(&self, message: &str) -> Result<(), Error>;
+    /// Checks the configuration up front so mistakes surface when the settings are loaded.
+    fn validate(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn send_message(&self, message: &Message) -> Result<(), Error>;
+
+    /// Receives every successfully polled rate, for notifiers that also act as a data sink.
+    async fn record_rate(&self, _monitor: &str, _rate: f64) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// How important an alert is, used by `[[rules]]` to pick the notifiers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Recoveries and other informational messages.
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A notification about the CNH/CNY rate crossing the warning threshold.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Message {
+    /// A short identifier of the monitor raising the alert, usable in topics and file names.
+    pub monitor: String,
+    pub text: String,
+    pub under_threshold: bool,
+    pub rate: f64,
+    #[serde(default)]
+    pub severity: Severity,
+    /// The name of the threshold level the alert is about, if any.
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Sent during a quiet window, notifiers lower the priority or mute it where supported.
+    #[serde(default)]
+    pub downgraded: bool,
+    /// About a level the rate recovered from rather than one it fell below,
+    /// even when it is still below a lower level.
+    #[serde(default)]
+    pub recovered: bool,
+}
+
+impl Message {
+    /// Breaches are warnings and recoveries informational.
+    pub fn new(monitor: &str, text: &str, under_threshold: bool, rate: f64) -> Self {
+        Self {
+            monitor: monitor.to_string(),
+            text: text.to_string(),
+            under_threshold,
+            rate,
+            severity: if under_threshold {
+                Severity::Warning
+            } else {
+                Severity::Info
+            },
+            level: None,
+            downgraded: false,
+            recovered: false,
+        }
+    }
+
+    pub fn recovered(mut self) -> Self {
+        self.recovered = true;
+        self
+    }
+
+    pub fn with_level(mut self, level: &str, severity: Severity) -> Self {
+        self.level = Some(level.to_string());
+        self.severity = severity;
+        self
+    }
+
+    /// Replaces `{monitor}`, `{text}`, `{under_threshold}`, `{rate}`, `{severity}`
+    /// and `{level}` in a template.
+    pub fn render(&self, template: &str) -> String {
+        template
+            .replace("{monitor}", &self.monitor)
+            .replace("{text}", &self.text)
+            .replace("{under_threshold}", &self.under_threshold.to_string())
+            .replace("{rate}", &self.rate.to_string())
+            .replace("{severity}", &self.severity.to_string())
+            .replace("{level}", self.level.as_deref().unwrap_or_default())
+    }
+}
+
+/// The title used by notifiers with a title field when none is configured.
+const DEFAULT_TITLE: &str = "CNH/CNY汇率提醒";
+
+/// Computes a base64 encoded HMAC-SHA256, as required by the DingTalk and Feishu robots.
+fn hmac_sha256_base64(key: &[u8], data: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// IM robots answer with HTTP 200 and report failures in an `errcode` or `code` field.
+async fn check_robot_response(response: reqwest::Response) -> Result<(), Error> {
+    check_json_code(response, &["errcode", "code"], 0).await
+}
+
+/// Turns a non-success status into [`Error::HttpStatus`], keeping the `Retry-After` delay.
+fn check_status(response: reqwest::Response) -> Result<reqwest::Response, Error> {
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs);
+    response
+        .error_for_status()
+        .context(HttpStatusSnafu { retry_after })
+}
+
+/// Fails with [`Error::Rejected`] when the first of `fields` present in the JSON body isn't `success`.
+async fn check_json_code(
+    response: reqwest::Response,
+    fields: &[&str],
+    success: i64,
+) -> Result<(), Error> {
+    let text = check_status(response)?.text().await.context(NetworkSnafu)?;
+    let code = serde_json::from_str::<Value>(&text).ok().and_then(|json| {
+        fields
+            .iter()
+            .find_map(|field| json.get(field))
+            .and_then(Value::as_i64)
+    });
+    if code.is_some_and(|code| code != success) {
+        return RejectedSnafu { text }.fail();
+    }
+
+    Ok(())
+}
+
+/// Sends a single datagram to a local Unix socket such as `/dev/log`.
+#[cfg(unix)]
+async fn send_datagram(path: &str, data: &[u8]) -> std::io::Result<()> {
+    let socket = tokio::net::UnixDatagram::unbound()?;
+    socket.send_to(data, path).await?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn send_datagram(_path: &str, _data: &[u8]) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Unix sockets are not supported on this platform",
+    ))
 }
 
 #[derive(Debug, Snafu)]
@@ -28,10 +372,122 @@ pub enum Error {
     #[snafu(display("Failed to send the message: {}", source))]
     Network { source: reqwest::Error },
 
+    #[snafu(display("Failed to send the message: {}", source))]
+    HttpStatus {
+        source: reqwest::Error,
+        retry_after: Option<Duration>,
+    },
+
+    #[snafu(display("The server rejected the message: {}", text))]
+    Rejected { text: String },
+
+    #[snafu(display("Unexpected response ({}): {}", reason, text))]
+    UnexpectedResponse { reason: String, text: String },
+
+    #[snafu(display("Invalid JSONPath {}: {}", path, source))]
+    InvalidJsonPath {
+        source: serde_json_path::ParseError,
+        path: String,
+    },
+
+    #[snafu(display("Invalid regular expression {}: {}", pattern, source))]
+    InvalidRegex {
+        source: regex::Error,
+        pattern: String,
+    },
+
+    #[snafu(display("Invalid header name: {}", header))]
+    HeaderName { header: String },
+
     #[snafu(display("Failed to parse the header value: {}", source))]
     HeaderValue {
         source: reqwest::header::InvalidHeaderValue,
         header: String,
         value: String,
     },
+
+    #[snafu(display("Invalid email address {}: {}", address, source))]
+    Address {
+        source: lettre::address::AddressError,
+        address: String,
+    },
+
+    #[snafu(display("No email recipients configured"))]
+    NoRecipient,
+
+    #[snafu(display("Failed to build the email: {}", source))]
+    BuildEmail { source: lettre::error::Error },
+
+    #[snafu(display("Failed to send the email: {}", source))]
+    Smtp {
+        source: lettre::transport::smtp::Error,
+    },
+
+    #[snafu(display("Invalid MQTT QoS {}, expected 0, 1 or 2", qos))]
+    InvalidQos { qos: u8 },
+
+    #[snafu(display("Failed to queue the MQTT request: {}", source))]
+    MqttClient { source: rumqttc::ClientError },
+
+    #[snafu(display("Failed to publish to the MQTT broker: {}", source))]
+    MqttConnection {
+        #[snafu(source(from(rumqttc::ConnectionError, Box::new)))]
+        source: Box<rumqttc::ConnectionError>,
+    },
+
+    #[snafu(display("Failed to run {}: {}", program, source))]
+    Spawn {
+        source: std::io::Error,
+        program: String,
+    },
+
+    #[snafu(display("{} exited with {}: {}", program, status, stderr))]
+    ExitStatus {
+        program: String,
+        status: String,
+        stderr: String,
+    },
+
+    #[snafu(display("No response within {} seconds", timeout))]
+    Timeout { timeout: f64 },
+
+    #[snafu(display("Failed to write to {}: {}", path, source))]
+    Io {
+        source: std::io::Error,
+        path: String,
+    },
+}
+
+impl Error {
+    /// Whether sending again later may succeed: connection problems, timeouts, 5xx and 429.
+    pub fn is_transient(&self) -> bool {
+        let transient_status = |source: &reqwest::Error| {
+            source.status().is_some_and(|status| {
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            })
+        };
+
+        match self {
+            Error::Network { source } => {
+                source.is_connect() || source.is_timeout() || transient_status(source)
+            }
+            Error::HttpStatus { source, .. } => transient_status(source),
+            Error::Smtp { source } => {
+                !(source.is_permanent()
+                    || source.is_client()
+                    || source.is_response()
+                    || source.is_tls())
+            }
+            Error::MqttConnection { .. } | Error::Timeout { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// The delay requested by the server through `Retry-After`, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Error::HttpStatus { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
 }
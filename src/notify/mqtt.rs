@@ -0,0 +1,248 @@
+use rumqttc::{AsyncClient, Event, MqttOptions, Outgoing, Packet, QoS, Transport};
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::*;
+
+/// Publishes alerts, and optionally every polled rate, to an MQTT broker.
+#[derive(Deserialize, Serialize)]
+pub struct Mqtt {
+    host: String,
+    /// Defaults to 1883, or 8883 when `tls` is enabled.
+    port: Option<u16>,
+    client_id: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    #[serde(default)]
+    tls: bool,
+    /// Topic template for alerts, `{monitor}` is replaced by the monitor name.
+    #[serde(default = "default_topic")]
+    topic: String,
+    /// Topic template for every polled rate, published with the retain flag so
+    /// new subscribers immediately get the last value.
+    rate_topic: Option<String>,
+    /// 0, 1 or 2.
+    #[serde(default = "default_qos")]
+    qos: u8,
+    #[serde(default)]
+    retain: bool,
+}
+
+fn default_topic() -> String {
+    "forex/{monitor}/alert".to_string()
+}
+
+fn default_qos() -> u8 {
+    1
+}
+
+impl Debug for Mqtt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mqtt")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("tls", &self.tls)
+            .field("topic", &self.topic)
+            .field("rate_topic", &self.rate_topic)
+            .finish()
+    }
+}
+
+impl Mqtt {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(host: &str, port: Option<u16>, topic: &str, rate_topic: Option<&str>) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            client_id: None,
+            username: None,
+            password: None,
+            tls: false,
+            topic: topic.to_string(),
+            rate_topic: rate_topic.map(|s| s.to_string()),
+            qos: default_qos(),
+            retain: false,
+        }
+    }
+
+    fn get_qos(&self) -> Result<QoS, Error> {
+        match self.qos {
+            0 => Ok(QoS::AtMostOnce),
+            1 => Ok(QoS::AtLeastOnce),
+            2 => Ok(QoS::ExactlyOnce),
+            qos => InvalidQosSnafu { qos }.fail(),
+        }
+    }
+
+    fn get_options(&self) -> MqttOptions {
+        let port = self.port.unwrap_or(if self.tls { 8883 } else { 1883 });
+        let client_id = self
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("forex_notify-{}", std::process::id()));
+
+        let mut options = MqttOptions::new(client_id, &self.host, port);
+        if let (Some(username), Some(password)) = (self.username.as_ref(), self.password.as_ref()) {
+            options.set_credentials(username, password);
+        }
+        if self.tls {
+            options.set_transport(Transport::tls_with_default_config());
+        }
+
+        options
+    }
+
+    /// Connects, publishes a single payload, waits for the acknowledgement and disconnects.
+    async fn publish(&self, topic: &str, retain: bool, payload: Vec<u8>) -> Result<(), Error> {
+        let qos = self.get_qos()?;
+        let (client, mut eventloop) = AsyncClient::new(self.get_options(), 10);
+        client
+            .publish(topic, qos, retain, payload)
+            .await
+            .context(MqttClientSnafu)?;
+
+        loop {
+            match eventloop.poll().await.context(MqttConnectionSnafu)? {
+                Event::Outgoing(Outgoing::Publish(_)) if qos == QoS::AtMostOnce => {
+                    client.disconnect().await.context(MqttClientSnafu)?;
+                }
+                Event::Incoming(Packet::PubAck(_)) | Event::Incoming(Packet::PubComp(_)) => {
+                    client.disconnect().await.context(MqttClientSnafu)?;
+                }
+                Event::Outgoing(Outgoing::Disconnect) => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notify for Mqtt {
+    fn validate(&self) -> Result<(), Error> {
+        self.get_qos().map(|_| ())
+    }
+
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let topic = self.topic.replace("{monitor}", &message.monitor);
+        let payload = serde_json::to_vec(message).expect("Message is always serializable");
+
+        self.publish(&topic, self.retain, payload).await
+    }
+
+    async fn record_rate(&self, monitor: &str, rate: f64) -> Result<(), Error> {
+        let Some(rate_topic) = self.rate_topic.as_ref() else {
+            return Ok(());
+        };
+        let topic = rate_topic.replace("{monitor}", monitor);
+        let payload = json!({ "monitor": monitor, "rate": rate }).to_string();
+
+        self.publish(&topic, true, payload.into_bytes()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    async fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let header = stream.read_u8().await.unwrap();
+        let mut length = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = stream.read_u8().await.unwrap();
+            length |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.unwrap();
+        (header, body)
+    }
+
+    /// Accepts one client, acknowledges its QoS 1 publish and returns the publish header and body.
+    async fn mqtt_broker(listener: TcpListener) -> (u8, Vec<u8>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let (header, _) = read_packet(&mut stream).await;
+        assert_eq!(header >> 4, 1, "expected CONNECT");
+        stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+        let (header, body) = read_packet(&mut stream).await;
+        assert_eq!(header >> 4, 3, "expected PUBLISH");
+        let topic_length = u16::from_be_bytes([body[0], body[1]]) as usize;
+        let packet_id = &body[2 + topic_length..4 + topic_length];
+        stream
+            .write_all(&[0x40, 0x02, packet_id[0], packet_id[1]])
+            .await
+            .unwrap();
+
+        let (disconnect, _) = read_packet(&mut stream).await;
+        assert_eq!(disconnect >> 4, 14, "expected DISCONNECT");
+
+        (header, body)
+    }
+
+    fn topic_and_payload(body: &[u8]) -> (String, String) {
+        let topic_length = u16::from_be_bytes([body[0], body[1]]) as usize;
+        let topic = String::from_utf8(body[2..2 + topic_length].to_vec()).unwrap();
+        // Skip the packet identifier that follows the topic for QoS 1
+        let payload = String::from_utf8(body[4 + topic_length..].to_vec()).unwrap();
+        (topic, payload)
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let broker = tokio::spawn(mqtt_broker(listener));
+
+        let mqtt = Mqtt::new("127.0.0.1", Some(port), "fx/{monitor}/alert", None);
+        mqtt.send_message(&Message::new("cnh_cny", "below", true, 0.997))
+            .await
+            .unwrap();
+
+        let (header, body) = broker.await.unwrap();
+        assert_eq!(header & 0x01, 0, "alerts are not retained by default");
+        let (topic, payload) = topic_and_payload(&body);
+        assert_eq!(topic, "fx/cnh_cny/alert");
+        let payload: Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(payload["under_threshold"], true);
+        assert_eq!(payload["rate"], 0.997);
+    }
+
+    #[tokio::test]
+    async fn test_record_rate() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let broker = tokio::spawn(mqtt_broker(listener));
+
+        let mqtt = Mqtt::new(
+            "127.0.0.1",
+            Some(port),
+            "fx/{monitor}/alert",
+            Some("fx/{monitor}/rate"),
+        );
+        mqtt.record_rate("cnh_cny", 0.9985).await.unwrap();
+
+        let (header, body) = broker.await.unwrap();
+        assert_eq!(header & 0x01, 1, "rates are retained");
+        let (topic, payload) = topic_and_payload(&body);
+        assert_eq!(topic, "fx/cnh_cny/rate");
+        assert_eq!(payload, r#"{"monitor":"cnh_cny","rate":0.9985}"#);
+    }
+
+    #[test]
+    fn test_validate_qos() {
+        let mut mqtt = Mqtt::new("127.0.0.1", None, "fx/{monitor}/alert", None);
+        mqtt.qos = 3;
+        assert!(matches!(mqtt.validate(), Err(Error::InvalidQos { qos: 3 })));
+    }
+}
@@ -1,22 +1,109 @@
-use std::sync::OnceLock;
+use std::collections::HashMap;
 
 use reqwest::header::HeaderMap;
 use snafu::ResultExt;
 
 use super::*;
 
+/// The priority of downgraded messages, see [`Message::downgraded`].
+const LOW_PRIORITY: u8 = 2;
+
 #[derive(Deserialize, Serialize)]
 pub struct Ntfy {
+    /// The full topic URL, or the server base URL when `topic` is set.
     url: String,
+    /// Topic template such as `fx-{monitor}`; when set, messages are published as JSON.
+    topic: Option<String>,
     token: Option<String>,
     title: Option<String>,
     priority: Option<u8>,
+    /// Overrides `priority` when the rate falls below the threshold.
+    below_priority: Option<u8>,
+    /// Overrides `priority` when the rate recovers above the threshold.
+    recovery_priority: Option<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    click: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    actions: Vec<Action>,
+    markdown: Option<bool>,
+    icon: Option<String>,
+    delay: Option<String>,
+    email: Option<String>,
+}
+
+/// An action button attached to the notification, see <https://docs.ntfy.sh/publish/#action-buttons>.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Action {
+    View {
+        label: String,
+        url: String,
+        #[serde(default)]
+        clear: bool,
+    },
+    Http {
+        label: String,
+        url: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        method: Option<String>,
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        headers: HashMap<String, String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<String>,
+        #[serde(default)]
+        clear: bool,
+    },
+}
+
+impl Action {
+    /// Renders the action in the short `X-Actions` header format.
+    fn to_header(&self) -> String {
+        match self {
+            Action::View { label, url, clear } => {
+                format!("view, {}, {}, clear={}", quote(label), quote(url), clear)
+            }
+            Action::Http {
+                label,
+                url,
+                method,
+                headers,
+                body,
+                clear,
+            } => {
+                let mut parts = vec!["http".to_string(), quote(label), quote(url)];
+                if let Some(method) = method {
+                    parts.push(format!("method={}", quote(method)));
+                }
+                for (key, value) in headers {
+                    parts.push(format!("headers.{}={}", key, quote(value)));
+                }
+                if let Some(body) = body {
+                    parts.push(format!("body={}", quote(body)));
+                }
+                parts.push(format!("clear={}", clear));
+                parts.join(", ")
+            }
+        }
+    }
+}
+
+/// Quotes a value of the `X-Actions` header if it contains separators.
+fn quote(value: &str) -> String {
+    if !value.contains([',', ';', '=']) {
+        value.to_string()
+    } else if value.contains('"') {
+        format!("'{}'", value)
+    } else {
+        format!("\"{}\"", value)
+    }
 }
 
 impl Debug for Ntfy {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Ntfy")
             .field("url", &self.url)
+            .field("topic", &self.topic)
             .field("title", &self.title)
             .field("priority", &self.priority)
             .finish()
@@ -29,70 +116,151 @@ impl Ntfy {
     pub fn new(url: &str, token: Option<&str>, title: Option<&str>, priority: Option<u8>) -> Self {
         Self {
             url: url.to_string(),
+            topic: None,
             token: token.map(|s| s.to_string()),
             title: title.map(|s| s.to_string()),
             priority,
+            below_priority: None,
+            recovery_priority: None,
+            tags: Vec::new(),
+            click: None,
+            actions: Vec::new(),
+            markdown: None,
+            icon: None,
+            delay: None,
+            email: None,
         }
     }
 
-    fn get_headers(&self) -> Result<&HeaderMap, Error> {
-        static HEADER_MAP: OnceLock<reqwest::header::HeaderMap> = OnceLock::new();
-
-        if let Some(header) = HEADER_MAP.get() {
-            Ok(header)
+    fn priority_for(&self, under_threshold: bool, downgraded: bool) -> Option<u8> {
+        if downgraded {
+            Some(LOW_PRIORITY)
+        } else if under_threshold {
+            self.below_priority.or(self.priority)
         } else {
-            let mut headers = reqwest::header::HeaderMap::new();
-            if let Some(token) = self.token.as_ref() {
-                headers.insert(
-                    "Authorization",
-                    format!("Bearer {}", token)
-                        .parse()
-                        .context(HeaderValueSnafu {
-                            header: "Authorization".to_string(),
-                            value: token.to_string(),
-                        })?,
-                );
-            }
-            if let Some(title) = self.title.as_ref() {
-                headers.insert(
-                    "X-Title",
-                    title.parse().context(HeaderValueSnafu {
-                        header: "X-Title".to_string(),
-                        value: title.to_string(),
-                    })?,
-                );
-            }
+            self.recovery_priority.or(self.priority)
+        }
+    }
 
-            if let Some(priority) = self.priority {
-                headers.insert(
-                    "X-Priority",
-                    priority.to_string().parse().context(HeaderValueSnafu {
-                        header: "X-Priority".to_string(),
-                        value: priority.to_string(),
-                    })?,
-                );
-            }
+    fn get_headers(&self, under_threshold: bool, downgraded: bool) -> Result<HeaderMap, Error> {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = self.token.as_ref() {
+            insert_header(&mut headers, "Authorization", format!("Bearer {}", token))?;
+        }
+        // In JSON mode everything except the credentials travels in the body
+        if self.topic.is_some() {
+            return Ok(headers);
+        }
+        if let Some(title) = self.title.as_ref() {
+            insert_header(&mut headers, "X-Title", title.to_string())?;
+        }
+        if let Some(priority) = self.priority_for(under_threshold, downgraded) {
+            insert_header(&mut headers, "X-Priority", priority.to_string())?;
+        }
+        if !self.tags.is_empty() {
+            insert_header(&mut headers, "X-Tags", self.tags.join(","))?;
+        }
+        if let Some(click) = self.click.as_ref() {
+            insert_header(&mut headers, "X-Click", click.to_string())?;
+        }
+        if !self.actions.is_empty() {
+            let actions = self
+                .actions
+                .iter()
+                .map(Action::to_header)
+                .collect::<Vec<_>>()
+                .join("; ");
+            insert_header(&mut headers, "X-Actions", actions)?;
+        }
+        if let Some(markdown) = self.markdown {
+            insert_header(&mut headers, "X-Markdown", markdown.to_string())?;
+        }
+        if let Some(icon) = self.icon.as_ref() {
+            insert_header(&mut headers, "X-Icon", icon.to_string())?;
+        }
+        if let Some(delay) = self.delay.as_ref() {
+            insert_header(&mut headers, "X-Delay", delay.to_string())?;
+        }
+        if let Some(email) = self.email.as_ref() {
+            insert_header(&mut headers, "X-Email", email.to_string())?;
+        }
 
-            let header = HEADER_MAP.get_or_init(|| headers);
-            Ok(header)
+        Ok(headers)
+    }
+
+    fn get_publish<'a>(&'a self, topic: &'a str, message: &'a Message) -> Publish<'a> {
+        Publish {
+            topic,
+            message: &message.text,
+            title: self.title.as_deref(),
+            priority: self.priority_for(message.under_threshold, message.downgraded),
+            tags: &self.tags,
+            click: self.click.as_deref(),
+            actions: &self.actions,
+            markdown: self.markdown,
+            icon: self.icon.as_deref(),
+            delay: self.delay.as_deref(),
+            email: self.email.as_deref(),
         }
     }
 }
 
+/// The body of a request to ntfy's JSON publishing API, see <https://docs.ntfy.sh/publish/#publish-as-json>.
+#[derive(Serialize)]
+struct Publish<'a> {
+    topic: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<u8>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    click: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    actions: &'a [Action],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    markdown: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delay: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<&'a str>,
+}
+
+fn insert_header(headers: &mut HeaderMap, name: &'static str, value: String) -> Result<(), Error> {
+    let header_value = value.parse().context(HeaderValueSnafu {
+        header: name.to_string(),
+        value: value.clone(),
+    })?;
+    headers.insert(name, header_value);
+    Ok(())
+}
+
 #[async_trait]
 impl Notify for Ntfy {
-    async fn send_message(&self, message: &str) -> Result<(), Error> {
-        let client = reqwest::Client::new();
-        let headers = self.get_headers()?;
-        let response = client
-            .post(&self.url)
-            .headers(headers.clone())
-            .body(message.to_string())
-            .send()
-            .await
-            .context(NetworkSnafu)?;
+    fn validate(&self) -> Result<(), Error> {
+        self.get_headers(true, false)?;
+        self.get_headers(false, false)?;
+        Ok(())
+    }
 
-        response.error_for_status().context(NetworkSnafu)?;
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let client = crate::http::client();
+        let headers = self.get_headers(message.under_threshold, message.downgraded)?;
+        let request = client.post(&self.url).headers(headers);
+        let request = match self.topic.as_ref() {
+            Some(topic) => {
+                let topic = topic.replace("{monitor}", &message.monitor);
+                request.json(&self.get_publish(&topic, message))
+            }
+            None => request.body(message.text.clone()),
+        };
+        let response = request.send().await.context(NetworkSnafu)?;
+
+        check_status(response)?;
 
         Ok(())
     }
@@ -122,12 +290,10 @@ mod tests {
             let mut stream = client.stream();
             while let Some(event) = stream.next().await {
                 match event {
-                    Ok(SSE::Event(event)) => {
-                        if event.event_type == "message" {
-                            if event.data.contains(token.as_str()) {
-                                return Ok(());
-                            }
-                        }
+                    Ok(SSE::Event(event))
+                        if event.event_type == "message" && event.data.contains(token.as_str()) =>
+                    {
+                        return Ok(());
                     }
                     Err(e) => {
                         return Err(format!("Failed to receive the message: {}", e));
@@ -142,7 +308,9 @@ mod tests {
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
 
         let ntfy = Ntfy::new(URL, None, Some("title"), Some(1));
-        ntfy.send_message(&message).await.unwrap();
+        ntfy.send_message(&Message::new("cnh_cny", &message, true, 0.998))
+            .await
+            .unwrap();
 
         // tokio select wait 3 seconds
         tokio::select! {
@@ -154,4 +322,202 @@ mod tests {
             },
         }
     }
+
+    #[tokio::test]
+    async fn test_send_message_independent_headers() {
+        let mut server = mockito::Server::new_async().await;
+
+        let alerts = server
+            .mock("POST", "/alerts")
+            .match_header("authorization", "Bearer alerts-token")
+            .match_header("x-title", "alerts")
+            .match_header("x-priority", "5")
+            .with_status(200)
+            .create_async()
+            .await;
+        let info = server
+            .mock("POST", "/info")
+            .match_header("authorization", "Bearer info-token")
+            .match_header("x-title", "info")
+            .match_header("x-priority", "2")
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let first = Ntfy::new(
+            &format!("{}/alerts", server.url()),
+            Some("alerts-token"),
+            Some("alerts"),
+            Some(5),
+        );
+        let second = Ntfy::new(
+            &format!("{}/info", server.url()),
+            Some("info-token"),
+            Some("info"),
+            Some(2),
+        );
+
+        first
+            .send_message(&Message::new("cnh_cny", "first", true, 0.997))
+            .await
+            .unwrap();
+        second
+            .send_message(&Message::new("cnh_cny", "second", true, 0.997))
+            .await
+            .unwrap();
+
+        alerts.assert_async().await;
+        info.assert_async().await;
+    }
+
+    #[test]
+    fn test_validate_invalid_header() {
+        let ntfy = Ntfy::new("http://example.com", None, Some("bad\ntitle"), None);
+        let error = ntfy.validate().unwrap_err();
+        assert!(matches!(error, Error::HeaderValue { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_send_downgraded() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/fx")
+            .match_header("x-priority", "2")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut ntfy = Ntfy::new(&format!("{}/fx", server.url()), None, None, Some(3));
+        ntfy.below_priority = Some(5);
+        ntfy.send_message(&Message::new("cnh_cny", "below", true, 0.997).downgrade())
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_message_features() {
+        let mut server = mockito::Server::new_async().await;
+
+        let below = server
+            .mock("POST", "/fx")
+            .match_header("x-priority", "5")
+            .match_header("x-tags", "chart_with_downwards_trend,warning")
+            .match_header("x-click", "https://example.com/chart")
+            .match_header(
+                "x-actions",
+                "view, Chart, https://example.com/chart, clear=false; \
+                 http, Ack, https://example.com/ack, method=POST, body='{\"ack\": true, \"by\": \"ntfy\"}', clear=true",
+            )
+            .match_header("x-markdown", "true")
+            .match_header("x-icon", "https://example.com/icon.png")
+            .match_header("x-delay", "30m")
+            .match_header("x-email", "fx@example.com")
+            .with_status(200)
+            .create_async()
+            .await;
+        let recovery = server
+            .mock("POST", "/fx")
+            .match_header("x-priority", "2")
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let mut ntfy = Ntfy::new(&format!("{}/fx", server.url()), None, None, Some(3));
+        ntfy.below_priority = Some(5);
+        ntfy.recovery_priority = Some(2);
+        ntfy.tags = vec![
+            "chart_with_downwards_trend".to_string(),
+            "warning".to_string(),
+        ];
+        ntfy.click = Some("https://example.com/chart".to_string());
+        ntfy.actions = vec![
+            Action::View {
+                label: "Chart".to_string(),
+                url: "https://example.com/chart".to_string(),
+                clear: false,
+            },
+            Action::Http {
+                label: "Ack".to_string(),
+                url: "https://example.com/ack".to_string(),
+                method: Some("POST".to_string()),
+                headers: HashMap::new(),
+                body: Some("{\"ack\": true, \"by\": \"ntfy\"}".to_string()),
+                clear: true,
+            },
+        ];
+        ntfy.markdown = Some(true);
+        ntfy.icon = Some("https://example.com/icon.png".to_string());
+        ntfy.delay = Some("30m".to_string());
+        ntfy.email = Some("fx@example.com".to_string());
+
+        ntfy.send_message(&Message::new("cnh_cny", "below", true, 0.997))
+            .await
+            .unwrap();
+        below.assert_async().await;
+
+        ntfy.send_message(&Message::new("cnh_cny", "recovered", false, 0.999))
+            .await
+            .unwrap();
+        recovery.assert_async().await;
+    }
+
+    #[test]
+    fn test_deserialize_actions() {
+        let ntfy: Ntfy = toml::from_str(
+            r#"
+            url = "https://ntfy.sh/fx"
+            tags = ["warning"]
+
+            [[actions]]
+            action = "view"
+            label = "Chart"
+            url = "https://example.com/chart"
+
+            [[actions]]
+            action = "http"
+            label = "Ack"
+            url = "https://example.com/ack"
+            headers = { Authorization = "Bearer token" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(ntfy.actions.len(), 2);
+        assert_eq!(
+            ntfy.actions[1].to_header(),
+            "http, Ack, https://example.com/ack, headers.Authorization=Bearer token, clear=false"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_message_json() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/")
+            .match_header("authorization", "Bearer token")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "topic": "fx-cnh_cny",
+                "message": "低于预设值",
+                "title": "汇率提醒",
+                "priority": 5,
+                "tags": ["warning"],
+            })))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let mut ntfy = Ntfy::new(&server.url(), Some("token"), Some("汇率提醒"), Some(3));
+        ntfy.topic = Some("fx-{monitor}".to_string());
+        ntfy.below_priority = Some(5);
+        ntfy.tags = vec!["warning".to_string()];
+        ntfy.validate().unwrap();
+
+        ntfy.send_message(&Message::new("cnh_cny", "低于预设值", true, 0.997))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
 }
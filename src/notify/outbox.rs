@@ -0,0 +1,387 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use tokio::{fs, io::AsyncWriteExt};
+use tracing::{debug, warn};
+
+use super::{retry::RetryPolicy, Error, IoSnafu, Message, Notifier};
+
+/// An on-disk queue per notifier of messages that could not be delivered yet.
+/// Pending messages are replayed in order before any newer one, and dropped
+/// to `expired.jsonl` once they are older than `max_age` seconds.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Outbox {
+    path: PathBuf,
+    #[serde(default = "default_max_age")]
+    max_age: u64,
+}
+
+fn default_max_age() -> u64 {
+    24 * 60 * 60
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Pending {
+    /// Unix timestamp in seconds of the first delivery attempt.
+    created: u64,
+    message: Message,
+}
+
+/// Queues are stored by notifier name, an unnamed notifier is only known by its
+/// position and would replay its queue to another one after the list changes.
+pub fn validate(notifiers: &[Notifier]) -> Result<(), String> {
+    match notifiers.iter().find(|notifier| notifier.name.is_none()) {
+        Some(notifier) => Err(format!(
+            "Notifier {:?} needs a name when the outbox is enabled",
+            notifier
+        )),
+        None => Ok(()),
+    }
+}
+
+/// One JSON value per line.
+fn to_lines<T: Serialize>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|value| serde_json::to_string(value).expect("Pending is always serializable") + "\n")
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Outbox {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(path: &Path, max_age: u64) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            max_age,
+        }
+    }
+
+    fn queue_path(&self, key: &str) -> PathBuf {
+        self.path.join(format!("{}.queue.jsonl", key))
+    }
+
+    /// Appends to a JSON Lines file of the outbox, creating it as needed.
+    async fn append(&self, file: &Path, lines: &str) -> Result<(), Error> {
+        let context = IoSnafu {
+            path: file.display().to_string(),
+        };
+        fs::create_dir_all(&self.path)
+            .await
+            .context(context.clone())?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(file)
+            .await
+            .context(context.clone())?;
+        file.write_all(lines.as_bytes()).await.context(context)
+    }
+
+    async fn load(&self, key: &str) -> Result<Vec<Pending>, Error> {
+        let path = self.queue_path(key);
+        let context = IoSnafu {
+            path: path.display().to_string(),
+        };
+        if !fs::try_exists(&path).await.context(context.clone())? {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&path).await.context(context)?;
+
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                serde_json::from_str(line)
+                    .inspect_err(|e| {
+                        warn!("Discarding a corrupted entry of {}: {}", path.display(), e)
+                    })
+                    .ok()
+            })
+            .collect())
+    }
+
+    async fn store(&self, key: &str, queue: &[Pending]) -> Result<(), Error> {
+        let path = self.queue_path(key);
+        let context = IoSnafu {
+            path: path.display().to_string(),
+        };
+        if queue.is_empty() {
+            if fs::try_exists(&path).await.context(context.clone())? {
+                fs::remove_file(&path).await.context(context)?;
+            }
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.path)
+            .await
+            .context(context.clone())?;
+        // Write to a temporary file first so a crash never leaves a truncated queue
+        let temp = path.with_extension("jsonl.tmp");
+        fs::write(&temp, to_lines(queue))
+            .await
+            .context(context.clone())?;
+        fs::rename(&temp, &path).await.context(context)
+    }
+
+    async fn expire(&self, key: &str, pending: &Pending) -> Result<(), Error> {
+        warn!(
+            "Dropping the message to {} queued since {}: {}",
+            key, pending.created, pending.message.text
+        );
+
+        let line = serde_json::json!({
+            "notifier": key,
+            "created": pending.created,
+            "expired": now(),
+            "message": pending.message,
+        });
+        self.append(&self.path.join("expired.jsonl"), &to_lines(&[line]))
+            .await
+    }
+
+    /// Queues `messages`, then delivers everything pending for the notifier in order.
+    /// Stops at the first transient failure and keeps the rest for the next call;
+    /// messages failing permanently are dropped and their error returned.
+    /// Returns the number of attempts spent on the delivered messages.
+    pub async fn deliver(
+        &self,
+        notifier: &Notifier,
+        key: &str,
+        messages: &[Message],
+        retry: &RetryPolicy,
+        timeout: f64,
+    ) -> Result<u32, Error> {
+        let pending = messages
+            .iter()
+            .map(|message| Pending {
+                created: now(),
+                message: message.clone(),
+            })
+            .collect::<Vec<_>>();
+        // Appended before reading the queue, so they are kept even when it can't be read.
+        let queued = if pending.is_empty() {
+            Ok(())
+        } else {
+            self.append(&self.queue_path(key), &to_lines(&pending))
+                .await
+        };
+        let mut queue = self.load(key).await?;
+        if queued.is_err() {
+            queue.extend(pending);
+        }
+
+        let mut total = 0;
+        let mut result = Ok(());
+        while let Some(pending) = queue.first() {
+            if now().saturating_sub(pending.created) > self.max_age {
+                self.expire(key, pending).await?;
+                queue.remove(0);
+                continue;
+            }
+
+            match notifier.deliver(&pending.message, retry, timeout).await {
+                Ok(attempts) => {
+                    total += attempts;
+                    debug!(
+                        "Delivered the queued message to {} after {} attempt(s)",
+                        key, attempts
+                    );
+                    queue.remove(0);
+                }
+                Err(e) if e.is_transient() => {
+                    result = Err(e);
+                    break;
+                }
+                Err(e) => {
+                    queue.remove(0);
+                    result = Err(e);
+                }
+            }
+        }
+
+        self.store(key, &queue).await?;
+        queued?;
+        result.map(|_| total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use mockito::Matcher;
+
+    use crate::notify::{
+        webhook::{Method, Webhook},
+        NotifyType,
+    };
+
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(uuid::Uuid::new_v4().to_string())
+    }
+
+    fn no_retry() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_names() {
+        let mut notifier = Notifier::from(NotifyType::Webhook(Webhook::new(
+            "http://example.com",
+            HashMap::new(),
+            None,
+            Method::Post,
+        )));
+        assert!(validate(std::slice::from_ref(&notifier)).is_err());
+
+        notifier.name = Some("webhook".to_string());
+        assert!(validate(&[notifier]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_replay_in_order() {
+        let mut server = mockito::Server::new_async().await;
+        let notifier = Notifier::from(NotifyType::Webhook(Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            Some("{rate}".to_string()),
+            Method::Post,
+        )));
+        let dir = temp_dir();
+        let outbox = Outbox::new(&dir, default_max_age());
+
+        let down = server
+            .mock("POST", "/hook")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let error = outbox
+            .deliver(
+                &notifier,
+                "0-webhook",
+                &[Message::new("cnh_cny", "below", true, 0.997)],
+                &no_retry(),
+                30.0,
+            )
+            .await
+            .unwrap_err();
+        assert!(error.is_transient());
+        down.assert_async().await;
+        assert!(dir.join("0-webhook.queue.jsonl").exists());
+
+        let first = server
+            .mock("POST", "/hook")
+            .match_body(Matcher::Exact("0.997".to_string()))
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+        let second = server
+            .mock("POST", "/hook")
+            .match_body(Matcher::Exact("0.999".to_string()))
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+        outbox
+            .deliver(
+                &notifier,
+                "0-webhook",
+                &[Message::new("cnh_cny", "above", false, 0.999)],
+                &no_retry(),
+                30.0,
+            )
+            .await
+            .unwrap();
+        first.assert_async().await;
+        second.assert_async().await;
+        assert!(!dir.join("0-webhook.queue.jsonl").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_expire() {
+        let server = mockito::Server::new_async().await;
+        let notifier = Notifier::from(NotifyType::Webhook(Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            None,
+            Method::Post,
+        )));
+        let dir = temp_dir();
+        let outbox = Outbox::new(&dir, 60);
+
+        outbox
+            .store(
+                "0-webhook",
+                &[Pending {
+                    created: now() - 120,
+                    message: Message::new("cnh_cny", "below", true, 0.997),
+                }],
+            )
+            .await
+            .unwrap();
+        outbox
+            .deliver(&notifier, "0-webhook", &[], &no_retry(), 30.0)
+            .await
+            .unwrap();
+
+        assert!(!dir.join("0-webhook.queue.jsonl").exists());
+        let expired = fs::read_to_string(dir.join("expired.jsonl")).unwrap();
+        assert!(expired.contains("\"notifier\":\"0-webhook\""));
+        assert!(expired.contains("\"text\":\"below\""));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queue_unreadable() {
+        let server = mockito::Server::new_async().await;
+        let notifier = Notifier::from(NotifyType::Webhook(Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            None,
+            Method::Post,
+        )));
+        let dir = temp_dir();
+        let outbox = Outbox::new(&dir, default_max_age());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("0-webhook.queue.jsonl"), b"\xff\n").unwrap();
+
+        let error = outbox
+            .deliver(
+                &notifier,
+                "0-webhook",
+                &[Message::new("cnh_cny", "below", true, 0.997)],
+                &no_retry(),
+                30.0,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Io { .. }));
+        // The new message is kept for when the queue is readable again
+        let queue = fs::read(dir.join("0-webhook.queue.jsonl")).unwrap();
+        assert!(String::from_utf8_lossy(&queue).contains("\"text\":\"below\""));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
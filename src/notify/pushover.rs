@@ -0,0 +1,143 @@
+use snafu::ResultExt;
+
+use super::*;
+
+/// A Pushover message, see <https://pushover.net/api>.
+#[derive(Deserialize, Serialize)]
+pub struct Pushover {
+    #[serde(default = "default_url")]
+    url: String,
+    /// The application API token.
+    token: String,
+    /// The user or group key.
+    user: String,
+    title: Option<String>,
+    /// From -2 (lowest) to 2 (emergency).
+    priority: Option<i8>,
+    sound: Option<String>,
+    device: Option<String>,
+}
+
+fn default_url() -> String {
+    "https://api.pushover.net/1/messages.json".to_string()
+}
+
+/// The priority of downgraded messages: no sound or vibration.
+const LOW_PRIORITY: i8 = -1;
+
+/// Emergency priority messages must be acknowledged and are repeated until they are.
+const EMERGENCY_RETRY: &str = "60";
+const EMERGENCY_EXPIRE: &str = "3600";
+
+impl Debug for Pushover {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pushover")
+            .field("title", &self.title)
+            .field("priority", &self.priority)
+            .field("sound", &self.sound)
+            .field("device", &self.device)
+            .finish()
+    }
+}
+
+impl Pushover {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, token: &str, user: &str, priority: Option<i8>) -> Self {
+        Self {
+            url: url.to_string(),
+            token: token.to_string(),
+            user: user.to_string(),
+            title: None,
+            priority,
+            sound: None,
+            device: None,
+        }
+    }
+
+    fn get_form(&self, message: &Message) -> Vec<(&'static str, String)> {
+        let mut form = vec![
+            ("token", self.token.clone()),
+            ("user", self.user.clone()),
+            (
+                "title",
+                self.title.as_deref().unwrap_or(DEFAULT_TITLE).to_string(),
+            ),
+            ("message", message.text.clone()),
+        ];
+        let priority = if message.downgraded {
+            Some(LOW_PRIORITY)
+        } else {
+            self.priority
+        };
+        if let Some(priority) = priority {
+            form.push(("priority", priority.to_string()));
+            if priority == 2 {
+                form.push(("retry", EMERGENCY_RETRY.to_string()));
+                form.push(("expire", EMERGENCY_EXPIRE.to_string()));
+            }
+        }
+        if let Some(sound) = self.sound.as_ref() {
+            form.push(("sound", sound.clone()));
+        }
+        if let Some(device) = self.device.as_ref() {
+            form.push(("device", device.clone()));
+        }
+
+        form
+    }
+}
+
+#[async_trait]
+impl Notify for Pushover {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let client = crate::http::client();
+        let response = client
+            .post(&self.url)
+            .form(&self.get_form(message))
+            .send()
+            .await
+            .context(NetworkSnafu)?;
+
+        check_json_code(response, &["status"], 1).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/1/messages.json")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("token".to_string(), "token".to_string()),
+                Matcher::UrlEncoded("user".to_string(), "user".to_string()),
+                Matcher::UrlEncoded("priority".to_string(), "2".to_string()),
+                Matcher::UrlEncoded("retry".to_string(), "60".to_string()),
+                Matcher::UrlEncoded("sound".to_string(), "siren".to_string()),
+            ]))
+            .with_status(200)
+            .with_body(r#"{"status":1,"request":"647d2300-702c-4b38-8b2f-d56326ae460b"}"#)
+            .create_async()
+            .await;
+
+        let mut pushover = Pushover::new(
+            &format!("{}/1/messages.json", server.url()),
+            "token",
+            "user",
+            Some(2),
+        );
+        pushover.sound = Some("siren".to_string());
+        pushover
+            .send_message(&Message::new("cnh_cny", "低于预设值", true, 0.997))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,109 @@
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::*;
+
+/// A PushPlus push to WeChat, see <https://www.pushplus.plus/doc/>.
+#[derive(Deserialize, Serialize)]
+pub struct PushPlus {
+    #[serde(default = "default_url")]
+    url: String,
+    token: String,
+    title: Option<String>,
+    /// `txt`, `html` or `markdown`.
+    template: Option<String>,
+    /// Sends to every subscriber of a group instead of only the token owner.
+    topic: Option<String>,
+    /// `wechat`, `webhook`, `cp`, `mail` or `sms`.
+    channel: Option<String>,
+}
+
+fn default_url() -> String {
+    "https://www.pushplus.plus/send".to_string()
+}
+
+impl Debug for PushPlus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PushPlus")
+            .field("title", &self.title)
+            .field("topic", &self.topic)
+            .field("channel", &self.channel)
+            .finish()
+    }
+}
+
+impl PushPlus {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, token: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            token: token.to_string(),
+            title: None,
+            template: None,
+            topic: None,
+            channel: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Notify for PushPlus {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let mut body = json!({
+            "token": self.token,
+            "title": self.title.as_deref().unwrap_or(DEFAULT_TITLE),
+            "content": message.text,
+        });
+        for (key, value) in [
+            ("template", &self.template),
+            ("topic", &self.topic),
+            ("channel", &self.channel),
+        ] {
+            if let Some(value) = value {
+                body[key] = json!(value);
+            }
+        }
+
+        let client = crate::http::client();
+        let response = client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .context(NetworkSnafu)?;
+
+        check_json_code(response, &["code"], 200).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/send")
+            .match_body(mockito::Matcher::Json(json!({
+                "token": "token",
+                "title": DEFAULT_TITLE,
+                "content": "低于预设值",
+                "topic": "forex",
+            })))
+            .with_status(200)
+            .with_body(r#"{"code":200,"msg":"请求成功","data":"1"}"#)
+            .create_async()
+            .await;
+
+        let mut pushplus = PushPlus::new(&format!("{}/send", server.url()), "token");
+        pushplus.topic = Some("forex".to_string());
+        pushplus
+            .send_message(&Message::new("cnh_cny", "低于预设值", true, 0.997))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,316 @@
+use std::{future::Future, time::Duration};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::Error;
+
+/// Exponential backoff for transient delivery failures, see [`Error::is_transient`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Seconds to wait before the first retry.
+    #[serde(default = "default_initial_backoff")]
+    pub initial_backoff: f64,
+    /// Upper bound in seconds for the computed backoff.
+    #[serde(default = "default_max_backoff")]
+    pub max_backoff: f64,
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    /// Randomises each backoff between half and all of its value.
+    #[serde(default = "default_jitter")]
+    pub jitter: bool,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff() -> f64 {
+    1.0
+}
+
+fn default_max_backoff() -> f64 {
+    60.0
+}
+
+fn default_multiplier() -> f64 {
+    2.0
+}
+
+fn default_jitter() -> bool {
+    true
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            initial_backoff: default_initial_backoff(),
+            max_backoff: default_max_backoff(),
+            multiplier: default_multiplier(),
+            jitter: default_jitter(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Backoffs that don't fit a `Duration` would panic on the first retry,
+    /// so they are rejected when the settings are loaded.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts < 1 {
+            return Err("Retry max_attempts must be at least 1".to_string());
+        }
+        for (field, seconds) in [
+            ("initial_backoff", self.initial_backoff),
+            ("max_backoff", self.max_backoff),
+        ] {
+            if !(seconds >= 0.0 && Duration::try_from_secs_f64(seconds).is_ok()) {
+                return Err(format!(
+                    "Retry {} must be a non-negative number of seconds, got {}",
+                    field, seconds
+                ));
+            }
+        }
+        if !(self.multiplier >= 0.0 && self.multiplier.is_finite()) {
+            return Err(format!(
+                "Retry multiplier must be a non-negative number, got {}",
+                self.multiplier
+            ));
+        }
+        Ok(())
+    }
+
+    /// The delay before retry number `retry`, starting at 1.
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = (self.initial_backoff * self.multiplier.powi(retry as i32 - 1))
+            .min(self.max_backoff)
+            .max(0.0);
+        let backoff = if self.jitter && backoff > 0.0 {
+            rand::thread_rng().gen_range(backoff / 2.0..=backoff)
+        } else {
+            backoff
+        };
+        Duration::from_secs_f64(backoff)
+    }
+
+    /// Runs `send` until it succeeds, fails permanently or runs out of attempts.
+    /// Returns the number of attempts made.
+    pub async fn run<F, Fut>(&self, mut send: F) -> Result<u32, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), Error>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match send().await {
+                Ok(()) => return Ok(attempt),
+                Err(e) if e.is_transient() && attempt < self.max_attempts => {
+                    // A server asking for a longer wait would otherwise stall the poll loop.
+                    let delay = e
+                        .retry_after()
+                        .map(|delay| delay.min(Duration::from_secs_f64(self.max_backoff.max(0.0))))
+                        .unwrap_or_else(|| self.backoff(attempt));
+                    debug!("Attempt {} failed, retrying in {:?}: {}", attempt, delay, e);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::notify::{
+        webhook::{Method, Webhook},
+        Message, Notifier, NotifyType,
+    };
+
+    use super::*;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: 0.01,
+            max_backoff: 0.05,
+            multiplier: 2.0,
+            jitter: false,
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(10), Duration::from_secs(60));
+
+        let policy = RetryPolicy::default();
+        for retry in 1..5 {
+            let backoff = policy.backoff(retry);
+            let full = Duration::from_secs_f64(2f64.powi(retry as i32 - 1));
+            assert!(backoff >= full / 2 && backoff <= full);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(RetryPolicy::default().validate().is_ok());
+        assert!(policy(0).validate().is_err());
+        for seconds in [-1.0, f64::NAN, f64::INFINITY, 1e20] {
+            let mut invalid = policy(3);
+            invalid.initial_backoff = seconds;
+            assert!(invalid.validate().is_err());
+            let mut invalid = policy(3);
+            invalid.max_backoff = seconds;
+            assert!(invalid.validate().is_err());
+        }
+        let mut invalid = policy(3);
+        invalid.multiplier = f64::INFINITY;
+        assert!(invalid.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient() {
+        let mut server = mockito::Server::new_async().await;
+
+        let unavailable = server
+            .mock("POST", "/hook")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let limited = server
+            .mock("POST", "/hook")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let accepted = server
+            .mock("POST", "/hook")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let notifier = Notifier::from(NotifyType::Webhook(Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            None,
+            Method::Post,
+        )));
+        let message = Message::new("cnh_cny", "", true, 0.997);
+
+        let attempts = notifier.deliver(&message, &policy(3), 30.0).await.unwrap();
+        assert_eq!(attempts, 3);
+        unavailable.assert_async().await;
+        limited.assert_async().await;
+        accepted.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_capped() {
+        let mut server = mockito::Server::new_async().await;
+
+        let limited = server
+            .mock("POST", "/hook")
+            .with_status(429)
+            .with_header("retry-after", "86400")
+            .expect(1)
+            .create_async()
+            .await;
+        let accepted = server
+            .mock("POST", "/hook")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let notifier = Notifier::from(NotifyType::Webhook(Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            None,
+            Method::Post,
+        )));
+        let message = Message::new("cnh_cny", "", true, 0.997);
+
+        // Waits max_backoff instead of a day
+        let attempts = tokio::time::timeout(
+            Duration::from_secs(5),
+            notifier.deliver(&message, &policy(2), 30.0),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(attempts, 2);
+        limited.assert_async().await;
+        accepted.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_permanent() {
+        let mut server = mockito::Server::new_async().await;
+
+        let rejected = server
+            .mock("POST", "/hook")
+            .with_status(400)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut notifier = Notifier::from(NotifyType::Webhook(Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            None,
+            Method::Post,
+        )));
+        // The per-notifier policy takes precedence over the global one
+        notifier.retry = Some(policy(5));
+        let message = Message::new("cnh_cny", "", true, 0.997);
+
+        let error = notifier
+            .deliver(&message, &policy(1), 30.0)
+            .await
+            .unwrap_err();
+        assert!(!error.is_transient());
+        rejected.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_give_up_after_max_attempts() {
+        let mut server = mockito::Server::new_async().await;
+
+        let unavailable = server
+            .mock("POST", "/hook")
+            .with_status(502)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let notifier = Notifier::from(NotifyType::Webhook(Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            None,
+            Method::Post,
+        )));
+        let message = Message::new("cnh_cny", "", true, 0.997);
+
+        let error = notifier
+            .deliver(&message, &policy(2), 30.0)
+            .await
+            .unwrap_err();
+        assert!(error.is_transient());
+        unavailable.assert_async().await;
+    }
+}
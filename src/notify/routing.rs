@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use super::{schedule::Schedule, Notifier, Severity};
+
+/// An entry of `[[rules]]`: sends alerts of at least `severity` to the named notifiers.
+/// Without any rule every alert goes to every notifier.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rule {
+    /// The lowest severity the rule applies to.
+    #[serde(default)]
+    pub severity: Severity,
+    /// Names of the notifiers, see [`Notifier::name`].
+    pub notifiers: Vec<String>,
+    /// Minimum seconds between two alerts routed by this rule.
+    pub cooldown: Option<u64>,
+    /// Quiet windows of the rule, see [`Schedule`].
+    pub schedule: Option<Schedule>,
+}
+
+impl Rule {
+    pub fn matches(&self, severity: Severity) -> bool {
+        severity >= self.severity
+    }
+
+    pub fn includes(&self, notifier: &Notifier) -> bool {
+        notifier
+            .name
+            .as_ref()
+            .is_some_and(|name| self.notifiers.contains(name))
+    }
+}
+
+/// Checks that notifier names are unique and every rule only references existing ones.
+pub fn validate(rules: &[Rule], notifiers: &[Notifier]) -> Result<(), String> {
+    let mut names = Vec::new();
+    for name in notifiers
+        .iter()
+        .filter_map(|notifier| notifier.name.as_ref())
+    {
+        // Names double as outbox file names, see `Notifier::key`.
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!(
+                "Invalid notifier name {:?}, only letters, digits, _ and - are allowed",
+                name
+            ));
+        }
+        if names.contains(&name) {
+            return Err(format!("Duplicate notifier name {}", name));
+        }
+        names.push(name);
+    }
+
+    for rule in rules.iter() {
+        if let Some(name) = rule.notifiers.iter().find(|name| !names.contains(name)) {
+            return Err(format!("Rule references unknown notifier {}", name));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::notify::{telegram::Telegram, NotifyType};
+
+    use super::*;
+
+    fn named(name: &str) -> Notifier {
+        let mut notifier = Notifier::from(NotifyType::Telegram(Telegram::new("token", "chat_id")));
+        notifier.name = Some(name.to_string());
+        notifier
+    }
+
+    fn rule(severity: Severity, notifiers: &[&str]) -> Rule {
+        Rule {
+            severity,
+            notifiers: notifiers.iter().map(|name| name.to_string()).collect(),
+            cooldown: None,
+            schedule: None,
+        }
+    }
+
+    #[test]
+    fn test_matches_and_includes() {
+        let rule = rule(Severity::Warning, &["ntfy"]);
+        assert!(!rule.matches(Severity::Info));
+        assert!(rule.matches(Severity::Warning));
+        assert!(rule.matches(Severity::Critical));
+
+        assert!(rule.includes(&named("ntfy")));
+        assert!(!rule.includes(&named("telegram")));
+        let unnamed = Notifier::from(NotifyType::Telegram(Telegram::new("token", "chat_id")));
+        assert!(!rule.includes(&unnamed));
+    }
+
+    #[test]
+    fn test_validate() {
+        let notifiers = vec![named("ntfy"), named("telegram")];
+        assert!(validate(&[rule(Severity::Info, &["ntfy"])], &notifiers).is_ok());
+        assert!(validate(&[rule(Severity::Info, &["slack"])], &notifiers).is_err());
+        assert!(validate(&[], &[named("ntfy"), named("ntfy")]).is_err());
+        for name in ["../x", "a/b", "", "告警"] {
+            assert!(validate(&[], &[named(name)]).is_err());
+        }
+        assert!(validate(&[], &[named("ntfy-main_2")]).is_ok());
+    }
+}
@@ -0,0 +1,174 @@
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use super::{Message, Severity};
+
+/// Quiet periods of a notifier or a rule, such as nights and weekends.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Schedule {
+    /// An IANA time zone such as `Asia/Shanghai`.
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+    pub windows: Vec<Window>,
+    /// What happens to alerts raised inside a window.
+    #[serde(default)]
+    pub action: QuietAction,
+}
+
+/// UTC, the time zone of schedules, reports and the watchdog when none is configured.
+pub fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+/// A daily period from `start` to `end`, crossing midnight when `end` isn't after `start`,
+/// so `start = end` covers whole days. Starts on `days` only, every day when empty.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Window {
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+/// Ordered from the least to the most restrictive.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuietAction {
+    /// Sends the alert anyway, at a low priority and without sound where supported.
+    Downgrade,
+    /// Holds the alerts back and sends them as one digest once the window is over.
+    Defer,
+    #[default]
+    Suppress,
+}
+
+impl Window {
+    fn contains(&self, day: Weekday, time: NaiveTime) -> bool {
+        let starts_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.start < self.end {
+            starts_on(day) && self.start <= time && time < self.end
+        } else {
+            // Either the evening of a start day or the morning after it.
+            (starts_on(day) && time >= self.start) || (starts_on(day.pred()) && time < self.end)
+        }
+    }
+}
+
+impl Schedule {
+    /// The action to take when `now` falls in one of the windows.
+    pub fn quiet_action(&self, now: DateTime<Utc>) -> Option<QuietAction> {
+        let local = now.with_timezone(&self.timezone);
+        let (day, time) = (local.weekday(), local.time());
+        self.windows
+            .iter()
+            .any(|window| window.contains(day, time))
+            .then_some(self.action)
+    }
+}
+
+impl Message {
+    /// The same alert, flagged for a low priority delivery.
+    pub fn downgrade(&self) -> Message {
+        let mut message = self.clone();
+        message.severity = Severity::Info;
+        message.downgraded = true;
+        message
+    }
+
+    /// Combines alerts deferred by a quiet window into one, oldest first.
+    pub fn digest(messages: &[Message]) -> Option<Message> {
+        let last = messages.last()?;
+        let text = messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut digest = Message::new(
+            &last.monitor,
+            &format!("静默期间的{}条通知:\n{}", messages.len(), text),
+            last.under_threshold,
+            last.rate,
+        );
+        digest.severity = messages
+            .iter()
+            .map(|message| message.severity)
+            .max()
+            .unwrap_or_default();
+        digest.level = last.level.clone();
+        digest.recovered = last.recovered;
+        Some(digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().to_utc()
+    }
+
+    fn schedule(windows: Vec<Window>) -> Schedule {
+        Schedule {
+            timezone: "Asia/Shanghai".parse().unwrap(),
+            windows,
+            action: QuietAction::Defer,
+        }
+    }
+
+    fn window(days: &[Weekday], start: &str, end: &str) -> Window {
+        Window {
+            days: days.to_vec(),
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_quiet_hours_across_midnight() {
+        let schedule = schedule(vec![window(&[], "23:00", "07:00")]);
+
+        // 2024-09-11 was a Wednesday, Shanghai is UTC+8.
+        assert_eq!(
+            schedule.quiet_action(at("2024-09-11T15:30:00Z")),
+            Some(QuietAction::Defer)
+        );
+        assert_eq!(
+            schedule.quiet_action(at("2024-09-11T22:59:00Z")),
+            Some(QuietAction::Defer)
+        );
+        assert_eq!(schedule.quiet_action(at("2024-09-11T23:00:00Z")), None);
+        assert_eq!(schedule.quiet_action(at("2024-09-11T14:59:00Z")), None);
+    }
+
+    #[test]
+    fn test_weekend() {
+        let schedule = schedule(vec![window(
+            &[Weekday::Sat, Weekday::Sun],
+            "00:00",
+            "00:00",
+        )]);
+
+        // Friday 23:59 and Monday 00:00 in Shanghai.
+        assert_eq!(schedule.quiet_action(at("2024-09-13T15:59:00Z")), None);
+        assert!(schedule.quiet_action(at("2024-09-13T16:00:00Z")).is_some());
+        assert!(schedule.quiet_action(at("2024-09-15T15:59:00Z")).is_some());
+        assert_eq!(schedule.quiet_action(at("2024-09-15T16:00:00Z")), None);
+    }
+
+    #[test]
+    fn test_digest() {
+        assert!(Message::digest(&[]).is_none());
+
+        let messages = vec![
+            Message::new("cnh_cny", "below", true, 0.994).with_level("warning", Severity::Warning),
+            Message::new("cnh_cny", "above", false, 0.999),
+        ];
+        let digest = Message::digest(&messages).unwrap();
+        assert_eq!(digest.text, "静默期间的2条通知:\nbelow\nabove");
+        assert_eq!(digest.severity, Severity::Warning);
+        assert!(!digest.under_threshold);
+        assert_eq!(digest.rate, 0.999);
+    }
+}
@@ -0,0 +1,110 @@
+use snafu::ResultExt;
+
+use super::*;
+
+/// A Server酱 (ServerChan) push to WeChat, see <https://sct.ftqq.com/>.
+#[derive(Deserialize, Serialize)]
+pub struct ServerChan {
+    #[serde(default = "default_url")]
+    url: String,
+    send_key: String,
+    title: Option<String>,
+    /// Forwards the message through a specific channel, e.g. `9` for the WeChat service account.
+    channel: Option<String>,
+}
+
+fn default_url() -> String {
+    "https://sctapi.ftqq.com".to_string()
+}
+
+impl Debug for ServerChan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerChan")
+            .field("title", &self.title)
+            .field("channel", &self.channel)
+            .finish()
+    }
+}
+
+impl ServerChan {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, send_key: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            send_key: send_key.to_string(),
+            title: None,
+            channel: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Notify for ServerChan {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let mut form = vec![
+            ("title", self.title.as_deref().unwrap_or(DEFAULT_TITLE)),
+            ("desp", message.text.as_str()),
+        ];
+        if let Some(channel) = self.channel.as_ref() {
+            form.push(("channel", channel.as_str()));
+        }
+
+        let client = crate::http::client();
+        let response = client
+            .post(format!(
+                "{}/{}.send",
+                self.url.trim_end_matches('/'),
+                self.send_key
+            ))
+            .form(&form)
+            .send()
+            .await
+            .context(NetworkSnafu)?;
+
+        check_json_code(response, &["code"], 0).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/SCT123.send")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("title".to_string(), DEFAULT_TITLE.to_string()),
+                Matcher::UrlEncoded("desp".to_string(), "低于预设值".to_string()),
+            ]))
+            .with_status(200)
+            .with_body(r#"{"code":0,"message":"","data":{"pushid":"1"}}"#)
+            .create_async()
+            .await;
+        let rejected = server
+            .mock("POST", "/bad.send")
+            .with_status(200)
+            .with_body(r#"{"code":40001,"message":"bad pushtoken"}"#)
+            .create_async()
+            .await;
+
+        let message = Message::new("cnh_cny", "低于预设值", true, 0.997);
+        ServerChan::new(&server.url(), "SCT123")
+            .send_message(&message)
+            .await
+            .unwrap();
+        mock.assert_async().await;
+
+        let error = ServerChan::new(&server.url(), "bad")
+            .send_message(&message)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Rejected { .. }));
+        rejected.assert_async().await;
+    }
+}
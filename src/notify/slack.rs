@@ -0,0 +1,121 @@
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::*;
+
+/// A Slack incoming webhook, see <https://api.slack.com/messaging/webhooks>.
+#[derive(Deserialize, Serialize)]
+pub struct Slack {
+    url: String,
+    title: Option<String>,
+}
+
+impl Debug for Slack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Slack").field("title", &self.title).finish()
+    }
+}
+
+impl Slack {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, title: Option<&str>) -> Self {
+        Self {
+            url: url.to_string(),
+            title: title.map(|s| s.to_string()),
+        }
+    }
+
+    /// Builds a Block Kit payload, `text` is kept as the fallback for notifications.
+    fn get_body(&self, message: &Message) -> Value {
+        let emoji = if message.under_threshold {
+            ":chart_with_downwards_trend:"
+        } else {
+            ":chart_with_upwards_trend:"
+        };
+
+        json!({
+            "text": message.text,
+            "blocks": [
+                {
+                    "type": "header",
+                    "text": {
+                        "type": "plain_text",
+                        "text": self.title.as_deref().unwrap_or(DEFAULT_TITLE),
+                    },
+                },
+                {
+                    "type": "section",
+                    "text": {
+                        "type": "mrkdwn",
+                        "text": format!("{} {}", emoji, message.text),
+                    },
+                },
+                {
+                    "type": "context",
+                    "elements": [{
+                        "type": "mrkdwn",
+                        "text": format!("*{}* {:.5}", message.monitor, message.rate),
+                    }],
+                },
+            ],
+        })
+    }
+}
+
+#[async_trait]
+impl Notify for Slack {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let client = crate::http::client();
+        let response = client
+            .post(&self.url)
+            .json(&self.get_body(message))
+            .send()
+            .await
+            .context(NetworkSnafu)?;
+
+        check_status(response)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/services/T000/B000/XXX")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "text": "CNH/CNY below",
+                "blocks": [
+                    { "type": "header", "text": { "type": "plain_text", "text": "FX" } },
+                    {
+                        "type": "section",
+                        "text": {
+                            "type": "mrkdwn",
+                            "text": ":chart_with_downwards_trend: CNH/CNY below",
+                        },
+                    },
+                ],
+            })))
+            .with_status(200)
+            .with_body("ok")
+            .create_async()
+            .await;
+
+        let slack = Slack::new(
+            &format!("{}/services/T000/B000/XXX", server.url()),
+            Some("FX"),
+        );
+        slack
+            .send_message(&Message::new("cnh_cny", "CNH/CNY below", true, 0.997))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+}
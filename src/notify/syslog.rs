@@ -0,0 +1,130 @@
+use snafu::ResultExt;
+
+use super::*;
+
+/// Sends alerts to the local syslog daemon as RFC 5424 messages with structured data.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Syslog {
+    #[serde(default = "default_socket")]
+    socket: String,
+    #[serde(default)]
+    facility: Facility,
+    #[serde(default = "default_app_name")]
+    app_name: String,
+}
+
+fn default_socket() -> String {
+    "/dev/log".to_string()
+}
+
+fn default_app_name() -> String {
+    "forex_notify".to_string()
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Facility {
+    #[default]
+    User,
+    Daemon,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    fn code(self) -> u8 {
+        match self {
+            Facility::User => 1,
+            Facility::Daemon => 3,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+/// Escapes `"`, `\` and `]` inside a structured data parameter value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace(']', "\\]")
+}
+
+impl Syslog {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(socket: &str, facility: Facility) -> Self {
+        Self {
+            socket: socket.to_string(),
+            facility,
+            app_name: default_app_name(),
+        }
+    }
+
+    fn format(&self, message: &Message) -> String {
+        // warning when falling below the threshold, notice on recovery
+        let severity = if message.under_threshold { 4 } else { 5 };
+        format!(
+            "<{}>1 - - {} {} alert [forex@32473 monitor=\"{}\" rate=\"{}\" under_threshold=\"{}\"] {}",
+            self.facility.code() * 8 + severity,
+            self.app_name,
+            std::process::id(),
+            escape(&message.monitor),
+            message.rate,
+            message.under_threshold,
+            message.text,
+        )
+    }
+}
+
+#[async_trait]
+impl Notify for Syslog {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        send_datagram(&self.socket, self.format(message).as_bytes())
+            .await
+            .context(IoSnafu {
+                path: self.socket.clone(),
+            })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use tokio::net::UnixDatagram;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let path = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        let syslog = Syslog::new(path.to_str().unwrap(), Facility::Local0);
+        syslog
+            .send_message(&Message::new("cnh_cny", "CNH/CNY below", true, 0.997))
+            .await
+            .unwrap();
+
+        let mut buf = vec![0; 1024];
+        let len = socket.recv(&mut buf).await.unwrap();
+        let received = String::from_utf8_lossy(&buf[..len]).to_string();
+        assert!(received.starts_with("<132>1 - - forex_notify "));
+        assert!(received.ends_with(
+            "alert [forex@32473 monitor=\"cnh_cny\" rate=\"0.997\" under_threshold=\"true\"] CNH/CNY below"
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -29,22 +29,27 @@ impl Telegram {
 
 #[async_trait]
 impl Notify for Telegram {
-    async fn send_message(&self, message: &str) -> Result<(), Error> {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
         let token = self.token.clone();
         let chat_id = self.chat_id.clone();
 
-        let client = reqwest::Client::new();
+        let mut form = vec![
+            ("chat_id", chat_id.as_str()),
+            ("text", message.text.as_str()),
+        ];
+        if message.downgraded {
+            form.push(("disable_notification", "true"));
+        }
+
+        let client = crate::http::client();
         let response = client
-            .post(format!(
-                "https://api.telegram.org/bot{}/sendMessage",
-                token
-            ))
-            .form(&[("chat_id", chat_id.as_str()), ("text", message)])
+            .post(format!("https://api.telegram.org/bot{}/sendMessage", token))
+            .form(&form)
             .send()
             .await
             .context(NetworkSnafu)?;
 
-        response.error_for_status().context(NetworkSnafu)?;
+        check_status(response)?;
 
         Ok(())
     }
@@ -57,8 +62,8 @@ mod tests {
     #[tokio::test]
     async fn test_send_message() {
         let telegram = Telegram::new("token", "chat_id");
-        let message = "Hello, world!";
-        let ret = telegram.send_message(message).await;
+        let message = Message::new("cnh_cny", "Hello, world!", true, 0.998);
+        let ret = telegram.send_message(&message).await;
         let error = ret.unwrap_err();
         dbg!(&error.to_string());
         assert!(error.to_string().contains("(404 Not Found)"));
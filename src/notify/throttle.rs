@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::Message;
+
+/// A token bucket: up to `capacity` messages in a burst, then one every `refill_interval` seconds.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RateLimit {
+    pub capacity: f64,
+    pub refill_interval: f64,
+}
+
+impl RateLimit {
+    /// An empty bucket or a refill interval that isn't positive would never let a message through
+    /// or never refill, so both are rejected when the settings are loaded.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.capacity >= 1.0 && self.capacity.is_finite()) {
+            return Err(format!(
+                "Rate limit capacity must be at least 1, got {}",
+                self.capacity
+            ));
+        }
+        if !(self.refill_interval > 0.0 && self.refill_interval.is_finite()) {
+            return Err(format!(
+                "Rate limit refill_interval must be a positive number of seconds, got {}",
+                self.refill_interval
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// When each rule, by index, last routed a message.
+    rules: HashMap<usize, Instant>,
+    /// Token buckets by notifier key.
+    buckets: HashMap<String, Bucket>,
+    /// When each alert, see [`dedup_key`], was last seen.
+    seen: HashMap<String, Instant>,
+    /// Alerts held back by a quiet window, by notifier key.
+    deferred: HashMap<String, Vec<Message>>,
+}
+
+/// Keeps noisy alerts from flooding the notifiers: deduplication, rule cooldowns,
+/// per-notifier rate limits and deferral during quiet windows.
+/// Shared by the concurrent deliveries of a dispatch.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    dedup_window: Option<Duration>,
+    /// The default rate limit, see [`super::Notifier::rate_limit`].
+    rate_limit: Option<RateLimit>,
+    state: Mutex<State>,
+}
+
+/// Alerts about the same monitor, level, direction and transition count as identical,
+/// whatever the rate in their text.
+fn dedup_key(message: &Message) -> String {
+    format!(
+        "{}/{}/{}/{}",
+        message.monitor,
+        message.level.as_deref().unwrap_or_default(),
+        message.under_threshold,
+        if message.recovered {
+            "recovery"
+        } else {
+            "breach"
+        }
+    )
+}
+
+impl Throttle {
+    pub fn new(dedup_window: Option<u64>, rate_limit: Option<RateLimit>) -> Self {
+        Self {
+            dedup_window: dedup_window.map(Duration::from_secs),
+            rate_limit,
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn default_rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+
+    /// Whether an identical alert was seen within the window, see [`Self::record_seen`].
+    pub fn is_duplicate(&self, message: &Message) -> bool {
+        self.is_duplicate_at(message, Instant::now())
+    }
+
+    fn is_duplicate_at(&self, message: &Message, now: Instant) -> bool {
+        let Some(window) = self.dedup_window else {
+            return false;
+        };
+
+        let mut state = self.state.lock().unwrap();
+        state
+            .seen
+            .retain(|_, seen| now.duration_since(*seen) < window);
+        state.seen.contains_key(&dedup_key(message))
+    }
+
+    /// Records an alert that was delivered or deferred by at least one notifier.
+    pub fn record_seen(&self, message: &Message) {
+        self.record_seen_at(message, Instant::now());
+    }
+
+    fn record_seen_at(&self, message: &Message, now: Instant) {
+        if self.dedup_window.is_some() {
+            let mut state = self.state.lock().unwrap();
+            state.seen.insert(dedup_key(message), now);
+        }
+    }
+
+    /// Whether the rule at `index` is out of its cooldown, see [`Self::start_cooldown`].
+    pub fn cooled_down(&self, index: usize, cooldown: Option<u64>) -> bool {
+        self.cooled_down_at(index, cooldown, Instant::now())
+    }
+
+    fn cooled_down_at(&self, index: usize, cooldown: Option<u64>, now: Instant) -> bool {
+        let Some(cooldown) = cooldown.map(Duration::from_secs) else {
+            return true;
+        };
+
+        let state = self.state.lock().unwrap();
+        state
+            .rules
+            .get(&index)
+            .is_none_or(|fired| now.duration_since(*fired) >= cooldown)
+    }
+
+    /// Records that the rule at `index` routed an alert that was delivered or deferred.
+    pub fn start_cooldown(&self, index: usize) {
+        self.start_cooldown_at(index, Instant::now());
+    }
+
+    fn start_cooldown_at(&self, index: usize, now: Instant) {
+        self.state.lock().unwrap().rules.insert(index, now);
+    }
+
+    pub fn defer(&self, key: &str, message: Message) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .deferred
+            .entry(key.to_string())
+            .or_default()
+            .push(message);
+    }
+
+    /// Puts back alerts taken by [`Self::take_deferred`] that couldn't be sent,
+    /// ahead of any deferred since.
+    pub fn restore_deferred(&self, key: &str, messages: Vec<Message>) {
+        if messages.is_empty() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let deferred = state.deferred.entry(key.to_string()).or_default();
+        deferred.splice(0..0, messages);
+    }
+
+    /// Removes and returns the alerts deferred for the notifier `key`.
+    pub fn take_deferred(&self, key: &str) -> Vec<Message> {
+        let mut state = self.state.lock().unwrap();
+        state.deferred.remove(key).unwrap_or_default()
+    }
+
+    /// Takes a token from the bucket of the notifier `key`, false when it's empty.
+    pub fn acquire(&self, key: &str, limit: &RateLimit) -> bool {
+        self.acquire_at(key, limit, Instant::now())
+    }
+
+    fn acquire_at(&self, key: &str, limit: &RateLimit, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let bucket = state.buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: limit.capacity,
+            updated: now,
+        });
+
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() / limit.refill_interval;
+        bucket.tokens = (bucket.tokens + refilled).min(limit.capacity);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::notify::Severity;
+
+    use super::*;
+
+    #[test]
+    fn test_dedup() {
+        let throttle = Throttle::new(Some(60), None);
+        let now = Instant::now();
+        let below = Message::new("cnh_cny", "below 99.7", true, 0.997);
+
+        assert!(!throttle.is_duplicate_at(&below, now));
+        // Checking alone doesn't record the alert.
+        assert!(!throttle.is_duplicate_at(&below, now));
+        throttle.record_seen_at(&below, now);
+        let again = Message::new("cnh_cny", "below 99.6", true, 0.996);
+        assert!(throttle.is_duplicate_at(&again, now + Duration::from_secs(30)));
+        let above = Message::new("cnh_cny", "above", false, 0.999);
+        assert!(!throttle.is_duplicate_at(&above, now + Duration::from_secs(30)));
+        assert!(!throttle.is_duplicate_at(&below, now + Duration::from_secs(61)));
+
+        let default = Throttle::default();
+        default.record_seen_at(&below, now);
+        assert!(!default.is_duplicate_at(&below, now));
+    }
+
+    #[test]
+    fn test_dedup_partial_recovery() {
+        let throttle = Throttle::new(Some(60), None);
+        let now = Instant::now();
+        let breach = Message::new("cnh_cny", "below notice", true, 0.997)
+            .with_level("notice", Severity::Info);
+        throttle.record_seen_at(&breach, now);
+
+        // Recovering from a lower level while still below notice isn't the same alert.
+        let recovery = Message::new("cnh_cny", "above warning, below notice", true, 0.996)
+            .with_level("notice", Severity::Info)
+            .recovered();
+        assert!(!throttle.is_duplicate_at(&recovery, now + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let throttle = Throttle::default();
+        let now = Instant::now();
+
+        assert!(throttle.cooled_down_at(0, Some(300), now));
+        // Checking alone doesn't start the cooldown.
+        assert!(throttle.cooled_down_at(0, Some(300), now));
+        throttle.start_cooldown_at(0, now);
+        assert!(!throttle.cooled_down_at(0, Some(300), now + Duration::from_secs(100)));
+        assert!(throttle.cooled_down_at(1, Some(300), now + Duration::from_secs(100)));
+        assert!(throttle.cooled_down_at(0, Some(300), now + Duration::from_secs(300)));
+        assert!(throttle.cooled_down_at(0, None, now + Duration::from_secs(100)));
+    }
+
+    #[test]
+    fn test_validate_rate_limit() {
+        let limit = |capacity, refill_interval| RateLimit {
+            capacity,
+            refill_interval,
+        };
+        assert!(limit(5.0, 12.0).validate().is_ok());
+        assert!(limit(0.0, 12.0).validate().is_err());
+        assert!(limit(f64::NAN, 12.0).validate().is_err());
+        assert!(limit(5.0, 0.0).validate().is_err());
+        assert!(limit(5.0, -1.0).validate().is_err());
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let throttle = Throttle::default();
+        let limit = RateLimit {
+            capacity: 2.0,
+            refill_interval: 10.0,
+        };
+        let now = Instant::now();
+
+        assert!(throttle.acquire_at("ntfy", &limit, now));
+        assert!(throttle.acquire_at("ntfy", &limit, now));
+        assert!(!throttle.acquire_at("ntfy", &limit, now));
+        assert!(throttle.acquire_at("telegram", &limit, now));
+        assert!(!throttle.acquire_at("ntfy", &limit, now + Duration::from_secs(5)));
+        assert!(throttle.acquire_at("ntfy", &limit, now + Duration::from_secs(10)));
+        assert!(!throttle.acquire_at("ntfy", &limit, now + Duration::from_secs(10)));
+    }
+}
@@ -1,15 +1,34 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use serde_json_path::JsonPath;
 use snafu::ResultExt;
 
 use super::*;
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize)]
 pub struct Webhook {
     url: String,
+    #[serde(default)]
     headers: HashMap<String, String>,
+    /// Query parameters appended to the URL, values support the same placeholders as `template`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    query: HashMap<String, String>,
     template: Option<String>,
     method: Method,
+    /// Selects the default `Content-Type` when `headers` doesn't set one.
+    #[serde(default)]
+    body: BodyMode,
+    auth: Option<Auth>,
+    signing: Option<Signing>,
+    /// Status codes treated as success, any 2xx when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    expected_status: Vec<u16>,
+    success: Option<SuccessCheck>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -17,6 +36,150 @@ pub enum Method {
     Get,
     Post,
     Put,
+    Patch,
+    Delete,
+}
+
+/// Checks the response body of endpoints that report failures with a 2xx status.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SuccessCheck {
+    /// A JSONPath such as `$.ok`, whose first match must equal `equals`.
+    json_path: Option<String>,
+    /// Defaults to `true`.
+    equals: Option<Value>,
+    /// A regular expression the body must match.
+    regex: Option<String>,
+}
+
+impl SuccessCheck {
+    fn get_json_path(&self) -> Result<Option<JsonPath>, Error> {
+        self.json_path
+            .as_ref()
+            .map(|path| {
+                JsonPath::parse(path).context(InvalidJsonPathSnafu {
+                    path: path.to_string(),
+                })
+            })
+            .transpose()
+    }
+
+    fn get_regex(&self) -> Result<Option<Regex>, Error> {
+        self.regex
+            .as_ref()
+            .map(|pattern| {
+                Regex::new(pattern).context(InvalidRegexSnafu {
+                    pattern: pattern.to_string(),
+                })
+            })
+            .transpose()
+    }
+
+    /// Returns why the body doesn't look like a success, if it doesn't.
+    fn check(&self, text: &str) -> Result<Option<String>, Error> {
+        if let Some(path) = self.get_json_path()? {
+            let expected = self.equals.clone().unwrap_or(Value::Bool(true));
+            let json = serde_json::from_str::<Value>(text).unwrap_or(Value::Null);
+            match path.query(&json).first() {
+                Some(value) if *value == expected => {}
+                Some(value) => {
+                    return Ok(Some(format!(
+                        "{} is {}, expected {}",
+                        self.json_path.as_deref().unwrap_or_default(),
+                        value,
+                        expected
+                    )))
+                }
+                None => {
+                    return Ok(Some(format!(
+                        "{} not found",
+                        self.json_path.as_deref().unwrap_or_default()
+                    )))
+                }
+            }
+        }
+        if let Some(regex) = self.get_regex()? {
+            if !regex.is_match(text) {
+                return Ok(Some(format!("body does not match {}", regex)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy)]
+pub enum BodyMode {
+    #[default]
+    Text,
+    Json,
+    Form,
+}
+
+impl BodyMode {
+    fn content_type(self) -> &'static str {
+        match self {
+            BodyMode::Text => "text/plain; charset=utf-8",
+            BodyMode::Json => "application/json",
+            BodyMode::Form => "application/x-www-form-urlencoded",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Auth {
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer {
+        token: String,
+    },
+}
+
+/// Signs the body with HMAC-SHA256 over `{timestamp}.{body}`, sending the hex digest
+/// as `sha256=<digest>` together with the unix timestamp so receivers can reject replays.
+#[derive(Deserialize, Serialize)]
+pub struct Signing {
+    secret: String,
+    #[serde(default = "default_signature_header")]
+    header: String,
+    #[serde(default = "default_timestamp_header")]
+    timestamp_header: String,
+}
+
+fn default_signature_header() -> String {
+    "X-Signature".to_string()
+}
+
+fn default_timestamp_header() -> String {
+    "X-Timestamp".to_string()
+}
+
+impl Signing {
+    fn sign(&self, timestamp: u64, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(format!("{}.{}", timestamp, body).as_bytes());
+        let digest = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        format!("sha256={}", digest)
+    }
+}
+
+impl Debug for Webhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Webhook")
+            .field("url", &self.url)
+            .field("method", &self.method)
+            .field("body", &self.body)
+            .field("signed", &self.signing.is_some())
+            .finish()
+    }
 }
 
 impl From<Method> for reqwest::Method {
@@ -25,31 +188,102 @@ impl From<Method> for reqwest::Method {
             Method::Get => reqwest::Method::GET,
             Method::Post => reqwest::Method::POST,
             Method::Put => reqwest::Method::PUT,
+            Method::Patch => reqwest::Method::PATCH,
+            Method::Delete => reqwest::Method::DELETE,
         }
     }
 }
 
 #[async_trait]
 impl Notify for Webhook {
-    async fn send_message(&self, message: &str) -> Result<(), Error> {
-        let client = reqwest::Client::new();
+    fn validate(&self) -> Result<(), Error> {
+        self.get_headers()?;
+        if let Some(success) = self.success.as_ref() {
+            success.get_json_path()?;
+            success.get_regex()?;
+        }
+        Ok(())
+    }
+
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let client = crate::http::client();
         let method = self.method.clone();
-        let mut request = client
-            .request(method.into(), &self.url)
-            .body(message.to_string());
+        let body = self.get_body(message);
+        let query = self
+            .query
+            .iter()
+            .map(|(key, value)| (key, message.render(value)))
+            .collect::<Vec<_>>();
 
-        for (key, value) in &self.headers {
-            request = request.header(key, value);
+        let mut headers = self.get_headers()?;
+        if let Some(signing) = self.signing.as_ref() {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            insert_header(
+                &mut headers,
+                &signing.timestamp_header,
+                &timestamp.to_string(),
+            )?;
+            insert_header(
+                &mut headers,
+                &signing.header,
+                &signing.sign(timestamp, &body),
+            )?;
         }
 
+        let mut request = client
+            .request(method.into(), &self.url)
+            .query(&query)
+            .headers(headers)
+            .body(body);
+        request = match self.auth.as_ref() {
+            Some(Auth::Basic { username, password }) => {
+                request.basic_auth(username, password.as_ref())
+            }
+            Some(Auth::Bearer { token }) => request.bearer_auth(token),
+            None => request,
+        };
+
         let response = request.send().await.context(NetworkSnafu)?;
 
-        response.error_for_status().context(NetworkSnafu)?;
+        let status = response.status();
+        let response = if self.expected_status.is_empty() {
+            check_status(response)?
+        } else if self.expected_status.contains(&status.as_u16()) {
+            response
+        } else {
+            return UnexpectedResponseSnafu {
+                reason: format!("status {}", status),
+                text: response.text().await.unwrap_or_default(),
+            }
+            .fail();
+        };
+
+        if let Some(success) = self.success.as_ref() {
+            let text = response.text().await.context(NetworkSnafu)?;
+            if let Some(reason) = success.check(&text)? {
+                return UnexpectedResponseSnafu { reason, text }.fail();
+            }
+        }
 
         Ok(())
     }
 }
 
+fn insert_header(headers: &mut HeaderMap, name: &str, value: &str) -> Result<(), Error> {
+    let header_name = name
+        .parse::<HeaderName>()
+        .map_err(|_| HeaderNameSnafu { header: name }.build())?;
+    let header_value = value.parse::<HeaderValue>().context(HeaderValueSnafu {
+        header: name.to_string(),
+        value: value.to_string(),
+    })?;
+    headers.insert(header_name, header_value);
+    Ok(())
+}
+
 impl Webhook {
     #[allow(dead_code)]
     pub fn new(
@@ -61,17 +295,36 @@ impl Webhook {
         Webhook {
             url: url.to_string(),
             headers,
+            query: HashMap::new(),
             template,
             method,
+            body: BodyMode::default(),
+            auth: None,
+            signing: None,
+            expected_status: Vec::new(),
+            success: None,
+        }
+    }
+
+    fn get_headers(&self) -> Result<HeaderMap, Error> {
+        let mut headers = HeaderMap::new();
+        for (key, value) in &self.headers {
+            insert_header(&mut headers, key, value)?;
+        }
+        if !headers.contains_key(CONTENT_TYPE) {
+            headers.insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static(self.body.content_type()),
+            );
         }
+
+        Ok(headers)
     }
 
-    pub fn generate_message(&self, under_threshold: bool, rate: f64) -> String {
-        self.template
-            .to_owned()
-            .unwrap_or_default()
-            .replace("{under_threshold}", &under_threshold.to_string())
-            .replace("{rate}", &rate.to_string())
+    /// Renders `template` with the placeholders of [`Message::render`], the plain text
+    /// of the message when unset so reports and status messages keep their content.
+    fn get_body(&self, message: &Message) -> String {
+        message.render(self.template.as_deref().unwrap_or("{text}"))
     }
 }
 
@@ -80,29 +333,41 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_generate_message() {
-        let webhook = Webhook {
-            url: "http://example.com".to_string(),
-            headers: HashMap::new(),
-            template: Some("CNH/CNY is below the warning threshold: {rate}".to_string()),
-            method: Method::Post,
-        };
+    fn test_get_body() {
+        let webhook = Webhook::new(
+            "http://example.com",
+            HashMap::new(),
+            Some("CNH/CNY is below the warning threshold: {rate}".to_string()),
+            Method::Post,
+        );
 
-        let message = webhook.generate_message(true, 6.5);
-        assert_eq!(message, "CNH/CNY is below the warning threshold: 6.5");
+        let body = webhook.get_body(&Message::new("cnh_cny", "below", true, 6.5));
+        assert_eq!(body, "CNH/CNY is below the warning threshold: 6.5");
     }
 
     #[test]
-    fn test_generate_message_default() {
-        let webhook = Webhook {
-            url: "http://example.com".to_string(),
-            headers: HashMap::new(),
-            template: None,
-            method: Method::Post,
-        };
+    fn test_get_body_severity_and_level() {
+        let webhook = Webhook::new(
+            "http://example.com",
+            HashMap::new(),
+            Some(r#"{"severity": "{severity}", "level": "{level}"}"#.to_string()),
+            Method::Post,
+        );
 
-        let message = webhook.generate_message(true, 6.5);
-        assert_eq!(message, "");
+        let message =
+            Message::new("cnh_cny", "below", true, 0.99).with_level("critical", Severity::Critical);
+        assert_eq!(
+            webhook.get_body(&message),
+            r#"{"severity": "critical", "level": "critical"}"#
+        );
+    }
+
+    #[test]
+    fn test_get_body_default() {
+        let webhook = Webhook::new("http://example.com", HashMap::new(), None, Method::Post);
+
+        let body = webhook.get_body(&Message::new("cnh_cny", "below", true, 6.5));
+        assert_eq!(body, "below");
     }
 
     #[test]
@@ -115,6 +380,12 @@ mod tests {
 
         let method: reqwest::Method = Method::Put.into();
         assert_eq!(method, reqwest::Method::PUT);
+
+        let method: reqwest::Method = Method::Patch.into();
+        assert_eq!(method, reqwest::Method::PATCH);
+
+        let method: reqwest::Method = Method::Delete.into();
+        assert_eq!(method, reqwest::Method::DELETE);
     }
 
     #[test]
@@ -134,19 +405,19 @@ mod tests {
             )
             .create();
 
-        let webhook = Webhook {
-            url: format!("{}{}", url, "/hello"),
-            headers: HashMap::from([("content-type".to_string(), "application/json".to_string())]),
-            template: Some(
+        let webhook = Webhook::new(
+            &format!("{}{}", url, "/hello"),
+            HashMap::from([("content-type".to_string(), "application/json".to_string())]),
+            Some(
                 "{
                 \"message\": \"hello\"
             }"
                 .to_string(),
             ),
-            method: Method::Post,
-        };
+            Method::Post,
+        );
 
-        let message = webhook.generate_message(true, 6.5);
+        let message = Message::new("cnh_cny", "", true, 6.5);
         tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
@@ -156,4 +427,192 @@ mod tests {
             });
         _mock.assert();
     }
+
+    #[test]
+    fn test_sign() {
+        let signing = Signing {
+            secret: "secret".to_string(),
+            header: default_signature_header(),
+            timestamp_header: default_timestamp_header(),
+        };
+        assert_eq!(
+            signing.sign(1700000000, "{\"rate\": 0.997}"),
+            "sha256=f8f4fcfd9de56becf4568d41439976fccb5ece27706ddf2563accfbfcab859f0"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_message_signed() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/hook")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("monitor".to_string(), "cnh_cny".to_string()),
+                mockito::Matcher::UrlEncoded("rate".to_string(), "0.997".to_string()),
+            ]))
+            .match_header("content-type", "application/json")
+            .match_header("authorization", "Basic dXNlcjpwYXNz")
+            .match_header(
+                "x-hub-timestamp",
+                mockito::Matcher::Regex("^\\d+$".to_string()),
+            )
+            .match_header(
+                "x-hub-signature",
+                mockito::Matcher::Regex("^sha256=[0-9a-f]{64}$".to_string()),
+            )
+            .match_body("{\"rate\": 0.997}")
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let mut webhook = Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            Some("{\"rate\": {rate}}".to_string()),
+            Method::Post,
+        );
+        webhook.query = HashMap::from([
+            ("monitor".to_string(), "{monitor}".to_string()),
+            ("rate".to_string(), "{rate}".to_string()),
+        ]);
+        webhook.body = BodyMode::Json;
+        webhook.auth = Some(Auth::Basic {
+            username: "user".to_string(),
+            password: Some("pass".to_string()),
+        });
+        webhook.signing = Some(Signing {
+            secret: "secret".to_string(),
+            header: "X-Hub-Signature".to_string(),
+            timestamp_header: "X-Hub-Timestamp".to_string(),
+        });
+        webhook.validate().unwrap();
+
+        webhook
+            .send_message(&Message::new("cnh_cny", "", true, 0.997))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_message_bearer_form() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("PUT", "/hook")
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_header("authorization", "Bearer token")
+            .match_body("rate=0.997")
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let mut webhook = Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            Some("rate={rate}".to_string()),
+            Method::Put,
+        );
+        webhook.body = BodyMode::Form;
+        webhook.auth = Some(Auth::Bearer {
+            token: "token".to_string(),
+        });
+
+        webhook
+            .send_message(&Message::new("cnh_cny", "", true, 0.997))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_message_success_check() {
+        let mut server = mockito::Server::new_async().await;
+
+        let _ok = server
+            .mock("PATCH", "/ok")
+            .with_status(200)
+            .with_body(r#"{"ok":true,"result":{"state":"sent"}}"#)
+            .create_async()
+            .await;
+        let _rejected = server
+            .mock("PATCH", "/rejected")
+            .with_status(200)
+            .with_body(r#"{"ok":false,"error":"chat not found"}"#)
+            .create_async()
+            .await;
+
+        let mut webhook = Webhook::new(
+            &format!("{}/ok", server.url()),
+            HashMap::new(),
+            None,
+            Method::Patch,
+        );
+        webhook.success = Some(SuccessCheck {
+            json_path: Some("$.ok".to_string()),
+            equals: None,
+            regex: Some("\"state\":\"sent\"".to_string()),
+        });
+        webhook.validate().unwrap();
+        let message = Message::new("cnh_cny", "", true, 0.997);
+        webhook.send_message(&message).await.unwrap();
+
+        webhook.url = format!("{}/rejected", server.url());
+        let error = webhook.send_message(&message).await.unwrap_err();
+        assert!(
+            matches!(error, Error::UnexpectedResponse { ref text, .. } if text.contains("chat not found"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_message_expected_status() {
+        let mut server = mockito::Server::new_async().await;
+
+        let _accepted = server
+            .mock("DELETE", "/hook")
+            .with_status(202)
+            .with_body("queued")
+            .create_async()
+            .await;
+
+        let mut webhook = Webhook::new(
+            &format!("{}/hook", server.url()),
+            HashMap::new(),
+            None,
+            Method::Delete,
+        );
+        let message = Message::new("cnh_cny", "", true, 0.997);
+
+        webhook.expected_status = vec![202];
+        webhook.send_message(&message).await.unwrap();
+
+        webhook.expected_status = vec![200, 204];
+        let error = webhook.send_message(&message).await.unwrap_err();
+        assert!(matches!(error, Error::UnexpectedResponse { ref text, .. } if text == "queued"));
+    }
+
+    #[test]
+    fn test_validate_success_check() {
+        let mut webhook = Webhook::new("http://example.com", HashMap::new(), None, Method::Post);
+        webhook.success = Some(SuccessCheck {
+            json_path: Some("$[".to_string()),
+            equals: None,
+            regex: None,
+        });
+        assert!(matches!(
+            webhook.validate(),
+            Err(Error::InvalidJsonPath { .. })
+        ));
+
+        webhook.success = Some(SuccessCheck {
+            json_path: None,
+            equals: None,
+            regex: Some("(".to_string()),
+        });
+        assert!(matches!(
+            webhook.validate(),
+            Err(Error::InvalidRegex { .. })
+        ));
+    }
 }
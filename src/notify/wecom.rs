@@ -0,0 +1,97 @@
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::*;
+
+/// A WeCom (企业微信) group robot, see <https://developer.work.weixin.qq.com/document/path/91770>.
+#[derive(Deserialize, Serialize)]
+pub struct WeCom {
+    /// The webhook URL including the `key` query parameter.
+    url: String,
+    title: Option<String>,
+}
+
+impl Debug for WeCom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeCom").field("title", &self.title).finish()
+    }
+}
+
+impl WeCom {
+    #[allow(dead_code)]
+    #[cfg(debug_assertions)]
+    pub fn new(url: &str, title: Option<&str>) -> Self {
+        Self {
+            url: url.to_string(),
+            title: title.map(|s| s.to_string()),
+        }
+    }
+
+    fn get_body(&self, message: &Message) -> Value {
+        let color = if message.under_threshold {
+            "warning"
+        } else {
+            "info"
+        };
+
+        json!({
+            "msgtype": "markdown",
+            "markdown": {
+                "content": format!(
+                    "### {}\n> <font color=\"{}\">{}</font>",
+                    self.title.as_deref().unwrap_or(DEFAULT_TITLE),
+                    color,
+                    message.text,
+                ),
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl Notify for WeCom {
+    async fn send_message(&self, message: &Message) -> Result<(), Error> {
+        let client = crate::http::client();
+        let response = client
+            .post(&self.url)
+            .json(&self.get_body(message))
+            .send()
+            .await
+            .context(NetworkSnafu)?;
+
+        check_robot_response(response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("POST", "/cgi-bin/webhook/send?key=key")
+            .match_body(mockito::Matcher::Json(json!({
+                "msgtype": "markdown",
+                "markdown": {
+                    "content": "### 汇率\n> <font color=\"info\">高于预设值</font>",
+                },
+            })))
+            .with_status(200)
+            .with_body(r#"{"errcode":0,"errmsg":"ok"}"#)
+            .create_async()
+            .await;
+
+        let wecom = WeCom::new(
+            &format!("{}/cgi-bin/webhook/send?key=key", server.url()),
+            Some("汇率"),
+        );
+        wecom
+            .send_message(&Message::new("cnh_cny", "高于预设值", false, 0.999))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+}
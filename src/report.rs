@@ -0,0 +1,306 @@
+use std::{collections::BTreeMap, str::FromStr, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::notify::{
+    dispatch::{Dispatcher, Outcome},
+    schedule::default_timezone,
+    Message,
+};
+
+/// An entry of `[[reports]]`: a summary of every monitor sent on a cron schedule.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Report {
+    /// A cron expression with seconds, e.g. `0 0 9,17 * * *` for 09:00 and 17:00.
+    pub cron: String,
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+    /// Names of the notifiers to send the report to, every notifier when empty.
+    #[serde(default)]
+    pub notifiers: Vec<String>,
+    /// Rendered for each monitor, see [`Stats::render`] for the placeholders.
+    #[serde(default = "default_template")]
+    pub template: String,
+}
+
+fn default_template() -> String {
+    "{monitor}: 开盘{open} 最高{high} 最低{low} 最新{last}，距阈值{distance}，期间告警{alerts}次"
+        .to_string()
+}
+
+/// What a monitor did since the previous report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub last: f64,
+    /// The next threshold the rate would cross, see [`crate::monitor::Ladder::next_threshold`].
+    pub threshold: f64,
+    pub alerts: u32,
+}
+
+impl Stats {
+    fn new(rate: f64, threshold: f64) -> Self {
+        Self {
+            open: rate,
+            high: rate,
+            low: rate,
+            last: rate,
+            threshold,
+            alerts: 0,
+        }
+    }
+
+    /// Replaces `{monitor}`, `{open}`, `{high}`, `{low}`, `{last}`, `{threshold}`,
+    /// `{distance}` and `{alerts}`, rates are shown ×100 like the alerts.
+    pub fn render(&self, monitor: &str, template: &str) -> String {
+        let rate = |rate: f64| format!("{:.3}", rate * 100.0);
+        template
+            .replace("{monitor}", monitor)
+            .replace("{open}", &rate(self.open))
+            .replace("{high}", &rate(self.high))
+            .replace("{low}", &rate(self.low))
+            .replace("{last}", &rate(self.last))
+            .replace("{threshold}", &rate(self.threshold))
+            .replace("{distance}", &rate(self.last - self.threshold))
+            .replace("{alerts}", &self.alerts.to_string())
+    }
+}
+
+/// Collects the [`Stats`] of every monitor between two runs of each report.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The stats since the previous run of each report, by report index.
+    periods: Vec<BTreeMap<String, Stats>>,
+    /// The latest rate of each monitor.
+    last: BTreeMap<String, f64>,
+}
+
+impl Tracker {
+    pub fn new(reports: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                periods: vec![BTreeMap::new(); reports],
+                last: BTreeMap::new(),
+            }),
+        }
+    }
+
+    pub fn record_rate(&self, monitor: &str, rate: f64, threshold: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.last.insert(monitor.to_string(), rate);
+        for period in state.periods.iter_mut() {
+            let stats = period
+                .entry(monitor.to_string())
+                .or_insert_with(|| Stats::new(rate, threshold));
+            stats.high = stats.high.max(rate);
+            stats.low = stats.low.min(rate);
+            stats.last = rate;
+            stats.threshold = threshold;
+        }
+    }
+
+    pub fn record_alert(&self, monitor: &str) {
+        let mut state = self.state.lock().unwrap();
+        for period in state.periods.iter_mut() {
+            if let Some(stats) = period.get_mut(monitor) {
+                stats.alerts += 1;
+            }
+        }
+    }
+
+    /// The latest rate of `monitor`, `None` before its first poll.
+    pub fn last(&self, monitor: &str) -> Option<f64> {
+        self.state.lock().unwrap().last.get(monitor).copied()
+    }
+
+    /// Returns the stats since the previous run of the report at `index`
+    /// and starts its new period opening at the last rates.
+    pub fn take(&self, index: usize) -> BTreeMap<String, Stats> {
+        let mut state = self.state.lock().unwrap();
+        let Some(period) = state.periods.get_mut(index) else {
+            return BTreeMap::new();
+        };
+        let taken = period.clone();
+        for stats in period.values_mut() {
+            *stats = Stats::new(stats.last, stats.threshold);
+        }
+        taken
+    }
+}
+
+impl Report {
+    pub fn schedule(&self) -> Result<cron::Schedule, cron::error::Error> {
+        cron::Schedule::from_str(&self.cron)
+    }
+
+    fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let schedule = self.schedule().ok()?;
+        let next = schedule.after(&time.with_timezone(&self.timezone)).next();
+        next.map(|next| next.to_utc())
+    }
+
+    /// One message summarising every monitor, `None` before the first poll.
+    pub fn build(&self, stats: &BTreeMap<String, Stats>) -> Option<Message> {
+        let (monitor, last) = stats.iter().next_back()?;
+        let text = stats
+            .iter()
+            .map(|(monitor, stats)| stats.render(monitor, &self.template))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(Message::new(
+            monitor,
+            &text,
+            last.last < last.threshold,
+            last.last,
+        ))
+    }
+}
+
+/// Sends every report on its schedule, each with the stats gathered since its previous run.
+pub async fn run_reports(reports: &[Report], tracker: &Tracker, dispatcher: &Dispatcher<'_>) {
+    if reports.is_empty() {
+        return std::future::pending().await;
+    }
+
+    let mut next = reports
+        .iter()
+        .map(|report| report.next_after(Utc::now()))
+        .collect::<Vec<_>>();
+    loop {
+        let Some(due) = next.iter().flatten().min().copied() else {
+            return std::future::pending().await;
+        };
+        let wait = (due - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        for (index, (report, next)) in reports.iter().zip(next.iter_mut()).enumerate() {
+            if *next != Some(due) {
+                continue;
+            }
+            *next = report.next_after(due);
+
+            let Some(message) = report.build(&tracker.take(index)) else {
+                continue;
+            };
+            info!("Sending the scheduled report: {}", report.cron);
+            for delivery in dispatcher.send_to(&report.notifiers, &message).await {
+                if let Outcome::Failed { error, .. } = delivery.outcome {
+                    warn!(
+                        "Failed to send the report use {}: {}",
+                        delivery.notifier, error
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker() {
+        let tracker = Tracker::new(1);
+        tracker.record_alert("cnh_cny");
+        tracker.record_rate("cnh_cny", 0.998, 0.997);
+        tracker.record_rate("cnh_cny", 0.999, 0.997);
+        tracker.record_rate("cnh_cny", 0.996, 0.997);
+        tracker.record_alert("cnh_cny");
+        tracker.record_rate("cnh_cny", 0.9975, 0.997);
+        assert_eq!(tracker.last("cnh_cny"), Some(0.9975));
+
+        let stats = tracker.take(0);
+        assert_eq!(
+            stats["cnh_cny"],
+            Stats {
+                open: 0.998,
+                high: 0.999,
+                low: 0.996,
+                last: 0.9975,
+                threshold: 0.997,
+                alerts: 1,
+            }
+        );
+
+        let stats = tracker.take(0);
+        assert_eq!(stats["cnh_cny"].open, 0.9975);
+        assert_eq!(stats["cnh_cny"].high, 0.9975);
+        assert_eq!(stats["cnh_cny"].alerts, 0);
+    }
+
+    #[test]
+    fn test_tracker_periods() {
+        // An hourly and a daily report.
+        let tracker = Tracker::new(2);
+        tracker.record_rate("cnh_cny", 0.996, 0.997);
+        tracker.record_alert("cnh_cny");
+        assert_eq!(tracker.take(0)["cnh_cny"].alerts, 1);
+
+        tracker.record_rate("cnh_cny", 0.999, 0.997);
+        assert_eq!(tracker.take(0)["cnh_cny"].alerts, 0);
+        let daily = tracker.take(1);
+        assert_eq!(daily["cnh_cny"].high, 0.999);
+        assert_eq!(daily["cnh_cny"].alerts, 1);
+    }
+
+    #[test]
+    fn test_build() {
+        let report = Report {
+            cron: "0 0 9,17 * * *".to_string(),
+            timezone: Tz::Asia__Shanghai,
+            notifiers: Vec::new(),
+            template: default_template(),
+        };
+        assert!(report.build(&BTreeMap::new()).is_none());
+
+        let stats = BTreeMap::from([(
+            "cnh_cny".to_string(),
+            Stats {
+                open: 0.998,
+                high: 0.999,
+                low: 0.996,
+                last: 0.9975,
+                threshold: 0.997,
+                alerts: 2,
+            },
+        )]);
+        let message = report.build(&stats).unwrap();
+        assert_eq!(
+            message.text,
+            "cnh_cny: 开盘99.800 最高99.900 最低99.600 最新99.750，距阈值0.050，期间告警2次"
+        );
+        assert!(!message.under_threshold);
+    }
+
+    #[test]
+    fn test_next_after() {
+        let report = Report {
+            cron: "0 0 9,17 * * *".to_string(),
+            timezone: Tz::Asia__Shanghai,
+            notifiers: Vec::new(),
+            template: default_template(),
+        };
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap().to_utc();
+
+        // 10:00 in Shanghai, the next report is at 17:00 local.
+        assert_eq!(
+            report.next_after(at("2024-09-11T02:00:00Z")),
+            Some(at("2024-09-11T09:00:00Z"))
+        );
+        assert_eq!(
+            report.next_after(at("2024-09-11T09:00:00Z")),
+            Some(at("2024-09-12T01:00:00Z"))
+        );
+    }
+}
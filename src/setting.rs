@@ -1,21 +1,102 @@
 use std::{
     env,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use config::{Config, ConfigError};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
-use crate::notify::NotifyType;
+use crate::{
+    heartbeat::Heartbeat,
+    http::{self, HttpConfig},
+    lifecycle::Lifecycle,
+    monitor::Level,
+    notify::{
+        outbox::{self, Outbox},
+        retry::RetryPolicy,
+        routing::{self, Rule},
+        throttle::RateLimit,
+        Notifier, Notify, Severity,
+    },
+    report::Report,
+    watchdog::Watchdog,
+};
 
 #[derive(Deserialize, Serialize)]
 pub struct Setting {
     pub log_level: String,
-    pub warning_threshold: f64,
+    /// A single warning level, ignored when `levels` is set.
+    pub warning_threshold: Option<f64>,
+    /// Threshold levels, each firing once when the rate falls below it.
+    #[serde(default)]
+    pub levels: Vec<Level>,
     pub api_key: String,
-    pub notifiers: Vec<NotifyType>,
+    pub notifiers: Vec<Notifier>,
+    /// Routes alerts to named notifiers by severity, every notifier gets every alert when empty.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Drops alerts identical to one sent within this many seconds.
+    pub dedup_window: Option<u64>,
+    /// The default rate limit of every notifier.
+    pub rate_limit: Option<RateLimit>,
     pub sleeptime: u64,
+    /// The default retry policy of every notifier.
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Seconds allowed for each delivery attempt of every notifier.
+    #[serde(default = "default_timeout")]
+    pub timeout: f64,
+    /// Keeps undelivered messages on disk, disabled when missing.
+    pub outbox: Option<Outbox>,
+    /// Scheduled summaries of every monitor.
+    #[serde(default)]
+    pub reports: Vec<Report>,
+    /// Status messages on startup and shutdown.
+    #[serde(default)]
+    pub lifecycle: Lifecycle,
+    /// Alerts when the forex provider keeps failing, disabled when missing.
+    pub watchdog: Option<Watchdog>,
+    /// Pings an outside dead man's switch after every poll, disabled when missing.
+    pub heartbeat: Option<Heartbeat>,
+    /// The shared HTTP client used by the forex provider and the notifiers.
+    #[serde(default)]
+    pub http: HttpConfig,
+}
+
+fn default_timeout() -> f64 {
+    30.0
+}
+
+/// Fails unless `seconds` is a positive duration, which `Duration::from_secs_f64` would panic on.
+fn check_seconds(what: &str, seconds: f64) -> Result<(), ConfigError> {
+    if seconds > 0.0 && Duration::try_from_secs_f64(seconds).is_ok() {
+        return Ok(());
+    }
+    Err(ConfigError::Message(format!(
+        "{} must be a positive number of seconds, got {}",
+        what, seconds
+    )))
+}
+
+/// Fails on the first of `names` that isn't the name of a notifier.
+fn check_notifiers(
+    owner: &str,
+    names: &[String],
+    notifiers: &[Notifier],
+) -> Result<(), ConfigError> {
+    match names.iter().find(|name| {
+        !notifiers
+            .iter()
+            .any(|notifier| notifier.name.as_ref() == Some(*name))
+    }) {
+        Some(name) => Err(ConfigError::Message(format!(
+            "{} references unknown notifier {}",
+            owner, name
+        ))),
+        None => Ok(()),
+    }
 }
 
 impl Setting {
@@ -27,13 +108,88 @@ impl Setting {
             .add_source(config::Environment::with_prefix("FOREX_NOTIFY"))
             .build()?;
 
-        let settings: Setting = settings.try_deserialize()?;
+        let mut settings: Setting = settings.try_deserialize()?;
         if settings.notifiers.is_empty() {
             return Err(ConfigError::Message("No notifiers found".to_string()));
         }
+        if settings.levels().is_empty() {
+            return Err(ConfigError::Message(
+                "Either warning_threshold or levels must be set".to_string(),
+            ));
+        }
+        routing::validate(&settings.rules, &settings.notifiers).map_err(ConfigError::Message)?;
+        check_seconds("timeout", settings.timeout)?;
+        for notifier in settings.notifiers.iter() {
+            if let Some(timeout) = notifier.timeout {
+                check_seconds(&format!("The timeout of {:?}", notifier), timeout)?;
+            }
+        }
+        for retry in std::iter::once(&settings.retry)
+            .chain(settings.notifiers.iter().filter_map(|n| n.retry.as_ref()))
+        {
+            retry.validate().map_err(ConfigError::Message)?;
+        }
+        for rate_limit in settings.rate_limit.iter().chain(
+            settings
+                .notifiers
+                .iter()
+                .filter_map(|n| n.rate_limit.as_ref()),
+        ) {
+            rate_limit.validate().map_err(ConfigError::Message)?;
+        }
+        if settings.outbox.is_some() {
+            outbox::validate(&settings.notifiers).map_err(ConfigError::Message)?;
+        }
+        for report in settings.reports.iter() {
+            report.schedule().map_err(|e| {
+                ConfigError::Message(format!("Invalid report cron {}: {}", report.cron, e))
+            })?;
+            check_notifiers("Report", &report.notifiers, &settings.notifiers)?;
+        }
+        check_notifiers(
+            "Lifecycle",
+            &settings.lifecycle.notifiers,
+            &settings.notifiers,
+        )?;
+        if let Some(watchdog) = settings.watchdog.as_ref() {
+            watchdog.validate().map_err(ConfigError::Message)?;
+            check_notifiers("Watchdog", &watchdog.notifiers, &settings.notifiers)?;
+        }
+        if let Some(heartbeat) = settings.heartbeat.as_ref() {
+            check_seconds("The heartbeat timeout", heartbeat.timeout)?;
+        }
+        http::init(&settings.http)
+            .map_err(|e| ConfigError::Message(format!("Invalid http settings: {}", e)))?;
+        for notifier in settings.notifiers.iter_mut() {
+            notifier.kind.validate().map_err(|e| {
+                ConfigError::Message(format!("Invalid notifier {:?}: {}", notifier, e))
+            })?;
+            if let Some(config) = notifier.http.as_ref() {
+                let client = config.merge(&settings.http).build().map_err(|e| {
+                    ConfigError::Message(format!("Invalid http settings of {:?}: {}", notifier, e))
+                })?;
+                notifier.client = Some(client);
+            }
+        }
 
         Ok(settings)
     }
+
+    /// The configured `levels`, or a single warning level at `warning_threshold`.
+    pub fn levels(&self) -> Vec<Level> {
+        if !self.levels.is_empty() {
+            return self.levels.clone();
+        }
+
+        self.warning_threshold
+            .map(|threshold| Level {
+                name: "warning".to_string(),
+                threshold,
+                severity: Severity::Warning,
+            })
+            .into_iter()
+            .collect()
+    }
 }
 
 fn get_config_path(env_name: &str, project_dir: Option<ProjectDirs>) -> PathBuf {
@@ -69,41 +225,220 @@ fn get_config_path(env_name: &str, project_dir: Option<ProjectDirs>) -> PathBuf
 mod tests {
     use std::collections::HashMap;
 
+    use chrono::NaiveTime;
+    use chrono_tz::Tz;
+
     use crate::notify::{
         ntfy::Ntfy,
+        schedule::{QuietAction, Schedule, Window},
         telegram::Telegram,
         webhook::{Method, Webhook},
+        NotifyType,
     };
 
     use super::*;
 
     #[test]
     fn test_serialize_setting() {
+        let named = |name: &str, kind: NotifyType| Notifier {
+            name: Some(name.to_string()),
+            ..kind.into()
+        };
+        let night = Schedule {
+            timezone: Tz::Asia__Shanghai,
+            windows: vec![Window {
+                days: Vec::new(),
+                start: NaiveTime::from_hms_opt(23, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            }],
+            action: QuietAction::Defer,
+        };
         let setting = Setting {
             log_level: "info".to_string(),
-            warning_threshold: 0.9980,
+            warning_threshold: Some(0.9980),
+            levels: vec![
+                Level {
+                    name: "warning".to_string(),
+                    threshold: 0.9980,
+                    severity: Severity::Warning,
+                },
+                Level {
+                    name: "critical".to_string(),
+                    threshold: 0.9950,
+                    severity: Severity::Critical,
+                },
+            ],
             api_key: "demo".to_string(),
             notifiers: vec![
-                NotifyType::Telegram(Telegram::new("token", "chat_id")),
-                NotifyType::Ntfy(Ntfy::new("url", Some("token"), Some("title"), Some(4))),
-                NotifyType::Webhook(Webhook::new(
-                    "http://example.com",
-                    HashMap::from([("Content-Type".to_string(), "application/json".to_string())]),
-                    Some(
-                        "{
-                        \"under_threshold\": {under_threshold},
-                        \"rate\": {rate}
-                    }"
-                        .to_string(),
-                    ),
-                    Method::Post,
-                )),
+                named(
+                    "telegram",
+                    NotifyType::Telegram(Telegram::new("token", "chat_id")),
+                ),
+                Notifier {
+                    rate_limit: Some(RateLimit {
+                        capacity: 5.0,
+                        refill_interval: 60.0,
+                    }),
+                    schedule: Some(night.clone()),
+                    ..named(
+                        "ntfy",
+                        NotifyType::Ntfy(Ntfy::new("url", Some("token"), Some("title"), Some(4))),
+                    )
+                },
+                Notifier {
+                    timeout: Some(10.0),
+                    http: Some(HttpConfig {
+                        proxy: Some("socks5h://127.0.0.1:1080".to_string()),
+                        ..Default::default()
+                    }),
+                    ..named(
+                        "webhook",
+                        NotifyType::Webhook(Webhook::new(
+                            "http://example.com",
+                            HashMap::from([(
+                                "Content-Type".to_string(),
+                                "application/json".to_string(),
+                            )]),
+                            Some(
+                                "{\n    \"under_threshold\": {under_threshold},\n    \"rate\": {rate}\n}\n"
+                                    .to_string(),
+                            ),
+                            Method::Post,
+                        )),
+                    )
+                },
+            ],
+            rules: vec![
+                Rule {
+                    severity: Severity::Info,
+                    notifiers: vec!["telegram".to_string()],
+                    cooldown: None,
+                    schedule: None,
+                },
+                Rule {
+                    severity: Severity::Critical,
+                    notifiers: vec!["ntfy".to_string(), "webhook".to_string()],
+                    cooldown: Some(600),
+                    schedule: Some(night),
+                },
             ],
+            dedup_window: Some(300),
+            rate_limit: Some(RateLimit {
+                capacity: 10.0,
+                refill_interval: 60.0,
+            }),
             sleeptime: 180,
+            retry: RetryPolicy::default(),
+            timeout: default_timeout(),
+            outbox: Some(Outbox::new(Path::new("outbox"), 24 * 60 * 60)),
+            reports: vec![Report {
+                cron: "0 0 9,17 * * *".to_string(),
+                timezone: Tz::Asia__Shanghai,
+                notifiers: vec!["telegram".to_string()],
+                template: "{monitor}: 最新{last}，距阈值{distance}".to_string(),
+            }],
+            lifecycle: Lifecycle {
+                startup: true,
+                shutdown: true,
+                notifiers: vec!["telegram".to_string()],
+            },
+            watchdog: Some(Watchdog {
+                max_failures: Some(3),
+                stale_after: Some(900),
+                timezone: Tz::Asia__Shanghai,
+                notifiers: vec!["telegram".to_string()],
+            }),
+            heartbeat: Some(Heartbeat {
+                url: "https://hc-ping.com/uuid".to_string(),
+                timeout: 10.0,
+            }),
+            http: HttpConfig {
+                proxy: None,
+                connect_timeout: Some(5.0),
+                timeout: Some(30.0),
+                user_agent: Some("forex_notify".to_string()),
+                root_certificates: Vec::new(),
+            },
         };
 
         let toml = toml::to_string(&setting).unwrap();
         // save to config.toml.example
         std::fs::write("config.toml.example", toml).unwrap();
     }
+
+    #[test]
+    fn test_check_seconds() {
+        assert!(check_seconds("timeout", 2.5).is_ok());
+        for seconds in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e30] {
+            assert!(check_seconds("timeout", seconds).is_err());
+        }
+    }
+
+    #[test]
+    fn test_deserialize_notifier_retry() {
+        let setting: Setting = Config::builder()
+            .add_source(config::File::from_str(
+                r#"
+                log_level = "info"
+                warning_threshold = 0.998
+                api_key = "demo"
+                sleeptime = 240
+
+                [retry]
+                max_attempts = 5
+
+                [[rules]]
+                notifiers = ["ntfy"]
+
+                [[rules]]
+                severity = "critical"
+                notifiers = ["telegram"]
+                cooldown = 600
+
+                [rate_limit]
+                capacity = 5
+                refill_interval = 12
+
+                [[notifiers]]
+                name = "telegram"
+                type = "Telegram"
+                token = "token"
+                chat_id = "chat_id"
+
+                [[notifiers]]
+                name = "ntfy"
+                type = "Ntfy"
+                url = "https://ntfy.sh/fx"
+                priority = 4
+
+                timeout = 5.0
+
+                [notifiers.retry]
+                max_attempts = 2
+                jitter = false
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        assert_eq!(setting.retry.max_attempts, 5);
+        assert!(matches!(setting.notifiers[0].kind, NotifyType::Telegram(_)));
+        assert_eq!(setting.timeout, 30.0);
+        assert_eq!(setting.levels()[0].threshold, 0.998);
+        assert_eq!(setting.rules[0].severity, Severity::Info);
+        assert_eq!(setting.rules[1].severity, Severity::Critical);
+        assert_eq!(setting.rules[1].cooldown, Some(600));
+        assert_eq!(setting.rate_limit.unwrap().capacity, 5.0);
+        routing::validate(&setting.rules, &setting.notifiers).unwrap();
+        assert_eq!(setting.notifiers[0].key(0), "telegram");
+        assert!(setting.notifiers[0].retry.is_none());
+        assert!(matches!(setting.notifiers[1].kind, NotifyType::Ntfy(_)));
+        let retry = setting.notifiers[1].retry.as_ref().unwrap();
+        assert_eq!(retry.max_attempts, 2);
+        assert!(!retry.jitter);
+        assert_eq!(setting.notifiers[1].timeout, Some(5.0));
+    }
 }
@@ -0,0 +1,281 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::notify::{schedule::default_timezone, Message, Severity};
+
+/// The `[watchdog]` section: tells the admins when the forex provider stops answering.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Watchdog {
+    /// Fires after this many failed polls in a row.
+    pub max_failures: Option<u32>,
+    /// Fires when no poll succeeded within this many seconds.
+    pub stale_after: Option<u64>,
+    /// The time zone of the outage time in the messages.
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+    /// Names of the notifiers to send the messages to, every notifier when empty.
+    #[serde(default)]
+    pub notifiers: Vec<String>,
+}
+
+impl Watchdog {
+    /// Catches a watchdog that can never fire or whose deadline can't be computed
+    /// when the settings are loaded.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_failures.is_none() && self.stale_after.is_none() {
+            return Err(
+                "Either max_failures or stale_after of the watchdog must be set".to_string(),
+            );
+        }
+        if let Some(seconds) = self.stale_after {
+            let valid = seconds > 0
+                && self
+                    .stale_window()
+                    .and_then(|window| Utc::now().checked_add_signed(window))
+                    .is_some();
+            if !valid {
+                return Err(format!(
+                    "stale_after of the watchdog must be a positive number of seconds, got {}",
+                    seconds
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn stale_window(&self) -> Option<TimeDelta> {
+        let seconds = i64::try_from(self.stale_after?).ok()?;
+        TimeDelta::try_seconds(seconds)
+    }
+}
+
+/// The polls of the provider since the last success.
+#[derive(Debug)]
+pub struct FeedHealth {
+    last_success: DateTime<Utc>,
+    /// The rate of the last successful poll, reported with the outage alert.
+    last_rate: Option<f64>,
+    failures: u32,
+    /// When the first of `failures` happened.
+    down_since: Option<DateTime<Utc>>,
+    alerted: bool,
+}
+
+impl FeedHealth {
+    /// Counts the time since `start` as healthy, so a feed down from the start still fires.
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            last_success: start,
+            last_rate: None,
+            failures: 0,
+            down_since: None,
+            alerted: false,
+        }
+    }
+
+    /// Records a failed poll, returning the outage alert once the watchdog fires.
+    pub fn failure(
+        &mut self,
+        watchdog: &Watchdog,
+        monitor: &str,
+        now: DateTime<Utc>,
+    ) -> Option<Message> {
+        self.failures += 1;
+        self.down_since.get_or_insert(now);
+        let too_many = watchdog
+            .max_failures
+            .is_some_and(|max| self.failures >= max);
+        if too_many {
+            return self.alert(watchdog, monitor);
+        }
+        self.check_stale(watchdog, monitor, now)
+    }
+
+    /// Returns the outage alert once no poll succeeded for `stale_after` seconds,
+    /// whether the polls failed or never came back.
+    pub fn check_stale(
+        &mut self,
+        watchdog: &Watchdog,
+        monitor: &str,
+        now: DateTime<Utc>,
+    ) -> Option<Message> {
+        let deadline = self.stale_deadline(watchdog)?;
+        if now < deadline {
+            return None;
+        }
+        self.alert(watchdog, monitor)
+    }
+
+    /// When the feed turns stale without another successful poll,
+    /// `None` without `stale_after` or when it is too far to represent.
+    pub fn stale_deadline(&self, watchdog: &Watchdog) -> Option<DateTime<Utc>> {
+        self.last_success
+            .checked_add_signed(watchdog.stale_window()?)
+    }
+
+    fn alert(&mut self, watchdog: &Watchdog, monitor: &str) -> Option<Message> {
+        if self.alerted {
+            return None;
+        }
+        self.alerted = true;
+
+        let since = self.down_since.unwrap_or(self.last_success);
+        let mut text = format!(
+            "汇率数据源自{}起不可用",
+            since.with_timezone(&watchdog.timezone).format("%H:%M")
+        );
+        if self.failures > 0 {
+            text.push_str(&format!("，已连续失败{}次", self.failures));
+        }
+        let rate = self.last_rate.unwrap_or_default();
+        Some(Message::new(monitor, &text, false, rate).with_level("feed", Severity::Critical))
+    }
+
+    /// Records a successful poll, returning the recovery message after an outage alert.
+    pub fn success(
+        &mut self,
+        watchdog: &Watchdog,
+        monitor: &str,
+        rate: f64,
+        now: DateTime<Utc>,
+    ) -> Option<Message> {
+        let previous = std::mem::replace(self, Self::new(now));
+        self.last_rate = Some(rate);
+        if !previous.alerted {
+            return None;
+        }
+
+        let text = format!(
+            "汇率数据源已恢复，自{}起共失败{}次，当前为:{:.3}",
+            previous
+                .down_since
+                .unwrap_or(previous.last_success)
+                .with_timezone(&watchdog.timezone)
+                .format("%H:%M"),
+            previous.failures,
+            rate * 100.0
+        );
+        Some(Message::new(monitor, &text, false, rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().to_utc()
+    }
+
+    fn watchdog(max_failures: Option<u32>, stale_after: Option<u64>) -> Watchdog {
+        Watchdog {
+            max_failures,
+            stale_after,
+            timezone: "Asia/Shanghai".parse().unwrap(),
+            notifiers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_max_failures() {
+        let watchdog = watchdog(Some(3), None);
+        let mut health = FeedHealth::new(at("2024-09-11T02:00:00Z"));
+
+        assert!(health
+            .failure(&watchdog, "cnh_cny", at("2024-09-11T02:32:00Z"))
+            .is_none());
+        assert!(health
+            .failure(&watchdog, "cnh_cny", at("2024-09-11T02:35:00Z"))
+            .is_none());
+        let message = health
+            .failure(&watchdog, "cnh_cny", at("2024-09-11T02:38:00Z"))
+            .unwrap();
+        assert_eq!(message.text, "汇率数据源自10:32起不可用，已连续失败3次");
+        assert_eq!(message.severity, Severity::Critical);
+        assert!(health
+            .failure(&watchdog, "cnh_cny", at("2024-09-11T02:41:00Z"))
+            .is_none());
+
+        let message = health
+            .success(&watchdog, "cnh_cny", 0.999, at("2024-09-11T02:44:00Z"))
+            .unwrap();
+        assert_eq!(
+            message.text,
+            "汇率数据源已恢复，自10:32起共失败4次，当前为:99.900"
+        );
+        assert!(health
+            .success(&watchdog, "cnh_cny", 0.999, at("2024-09-11T02:47:00Z"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_stale_after() {
+        let watchdog = watchdog(None, Some(600));
+        let mut health = FeedHealth::new(at("2024-09-11T02:00:00Z"));
+
+        // A blip that recovers within the window stays silent.
+        assert!(health
+            .failure(&watchdog, "cnh_cny", at("2024-09-11T02:05:00Z"))
+            .is_none());
+        assert!(health
+            .success(&watchdog, "cnh_cny", 0.999, at("2024-09-11T02:08:00Z"))
+            .is_none());
+
+        assert!(health
+            .failure(&watchdog, "cnh_cny", at("2024-09-11T02:15:00Z"))
+            .is_none());
+        let message = health
+            .failure(&watchdog, "cnh_cny", at("2024-09-11T02:18:00Z"))
+            .unwrap();
+        assert_eq!(message.rate, 0.999);
+    }
+
+    #[test]
+    fn test_stale_without_failures() {
+        let watchdog = watchdog(None, Some(600));
+        let mut health = FeedHealth::new(at("2024-09-11T02:00:00Z"));
+        health.success(&watchdog, "cnh_cny", 0.999, at("2024-09-11T02:32:00Z"));
+        assert_eq!(
+            health.stale_deadline(&watchdog),
+            Some(at("2024-09-11T02:42:00Z"))
+        );
+
+        // The poll hangs, nothing fails.
+        assert!(health
+            .check_stale(&watchdog, "cnh_cny", at("2024-09-11T02:41:00Z"))
+            .is_none());
+        let message = health
+            .check_stale(&watchdog, "cnh_cny", at("2024-09-11T02:42:00Z"))
+            .unwrap();
+        assert_eq!(message.text, "汇率数据源自10:32起不可用");
+        assert!(health
+            .check_stale(&watchdog, "cnh_cny", at("2024-09-11T02:50:00Z"))
+            .is_none());
+        assert!(health
+            .success(&watchdog, "cnh_cny", 0.999, at("2024-09-11T02:51:00Z"))
+            .is_some());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(watchdog(Some(3), None).validate().is_ok());
+        assert!(watchdog(None, Some(600)).validate().is_ok());
+        assert!(watchdog(None, None).validate().is_err());
+        assert!(watchdog(None, Some(0)).validate().is_err());
+        assert!(watchdog(None, Some(u64::MAX)).validate().is_err());
+        assert!(watchdog(None, Some(i64::MAX as u64 / 1000))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_stale_after_out_of_range() {
+        let watchdog = watchdog(Some(3), Some(u64::MAX));
+        let mut health = FeedHealth::new(at("2024-09-11T02:00:00Z"));
+        assert_eq!(health.stale_deadline(&watchdog), None);
+        assert!(health
+            .failure(&watchdog, "cnh_cny", at("2024-09-11T02:05:00Z"))
+            .is_none());
+    }
+}